@@ -61,4 +61,112 @@ pub enum DarkFlowError {
 
     #[msg("Deadline exceeded")]
     DeadlineExceeded,
+
+    #[msg("Invalid verifying key")]
+    InvalidVerifyingKey,
+
+    #[msg("Verifying key version does not match the version pinned by the pool")]
+    VerifyingKeyVersionMismatch,
+
+    #[msg("Invalid bonding curve parameters")]
+    InvalidCurveParams,
+
+    #[msg("Launch has not ended")]
+    LaunchNotEnded,
+
+    #[msg("Tokens already claimed")]
+    AlreadyClaimed,
+
+    #[msg("Invalid launch schedule")]
+    InvalidLaunchSchedule,
+
+    #[msg("Launch is not refundable")]
+    LaunchNotRefundable,
+
+    #[msg("Proceeds already withdrawn")]
+    ProceedsAlreadyWithdrawn,
+
+    #[msg("Invalid launch caps")]
+    InvalidLaunchCaps,
+
+    #[msg("Wallet purchase limit reached")]
+    WalletLimitExceeded,
+
+    #[msg("Launch ended under its soft cap")]
+    SoftCapNotMet,
+
+    #[msg("Solver is not registered")]
+    SolverNotRegistered,
+
+    #[msg("Solver is already registered")]
+    SolverAlreadyRegistered,
+
+    #[msg("Solver registry is full")]
+    SolverRegistryFull,
+
+    #[msg("Bond below the registry minimum")]
+    InsufficientBond,
+
+    #[msg("Solver is not an active registered solver")]
+    UnauthorizedSolver,
+
+    #[msg("Order has not expired")]
+    OrderNotExpired,
+
+    #[msg("Invalid batch")]
+    InvalidBatch,
+
+    #[msg("Batch has no feasible clearing price")]
+    BatchNotClearable,
+
+    #[msg("Revealed order parameters do not match the commitment")]
+    CommitmentMismatch,
+
+    #[msg("Position tree is full")]
+    PositionTreeFull,
+
+    #[msg("Merkle root is not a recent position tree root")]
+    UnknownMerkleRoot,
+
+    #[msg("Change position must be provided exactly when shares remain")]
+    ChangePositionMismatch,
+
+    #[msg("Not enough price history for the requested TWAP window")]
+    TwapWindowUnavailable,
+
+    #[msg("Invalid tick range")]
+    InvalidTickRange,
+
+    #[msg("Pool has too many initialized ticks")]
+    TickStateFull,
+
+    #[msg("Instruction does not match the pool's liquidity mode")]
+    PoolModeMismatch,
+
+    #[msg("Not enough liquidity to fill the swap")]
+    InsufficientLiquidity,
+
+    #[msg("Invalid amplification coefficient or ramp")]
+    InvalidAmplification,
+
+    #[msg("StableSwap invariant did not converge")]
+    InvariantNotConverged,
+
+    #[msg("Pool is in emergency mode; only withdrawals are allowed")]
+    EmergencyMode,
+
+    #[msg("Signer is not the pending pool authority")]
+    NotPendingAuthority,
+
+    #[msg("Pool has no treasury")]
+    TreasuryNotSet,
+
+    #[msg("Solver is unbonding")]
+    SolverUnbonding,
+
+    #[msg("Solver has not unbonded for the cooldown period")]
+    UnbondCooldownActive,
+
+    #[msg("Position liquidity is below the minimum")]
+    PositionTooSmall,
 }
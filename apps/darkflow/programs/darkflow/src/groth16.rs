@@ -0,0 +1,223 @@
+//! Groth16 verifier over BN254 using the Solana `alt_bn128` syscalls.
+//!
+//! Encoding follows the Ethereum precompiles (EIP-196/197), which is what
+//! the `alt_bn128` syscalls expect:
+//!   - G1 point: `x || y`, 32-byte big-endian coordinates (64 bytes)
+//!   - G2 point: `x_c1 || x_c0 || y_c1 || y_c0` (128 bytes)
+//!   - Scalars / public inputs: 32-byte big-endian field elements
+//!
+//! Proof layout (256 bytes): `A (G1) || B (G2) || C (G1)`
+//!
+//! Verifying key layout:
+//!   `alpha (G1) || beta (G2) || gamma (G2) || delta (G2) || IC[0..=n] (G1)`
+//! where `n` is the number of public inputs.
+
+use anchor_lang::solana_program::alt_bn128::prelude::{
+    alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing,
+};
+
+/// Size of a G1 point in bytes
+pub const G1_LEN: usize = 64;
+
+/// Size of a G2 point in bytes
+pub const G2_LEN: usize = 128;
+
+/// Size of a serialized proof in bytes
+pub const PROOF_LEN: usize = G1_LEN + G2_LEN + G1_LEN;
+
+/// Size of the fixed part of a verifying key (alpha, beta, gamma, delta)
+pub const VK_HEADER_LEN: usize = G1_LEN + 3 * G2_LEN;
+
+/// BN254 base field modulus `q` (big-endian), used to negate G1 points
+const BASE_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// BN254 scalar field modulus `r` (big-endian); public inputs must be below it
+const SCALAR_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// A parsed Groth16 proof
+pub struct Groth16Proof<'a> {
+    pub a: &'a [u8],
+    pub b: &'a [u8],
+    pub c: &'a [u8],
+}
+
+impl<'a> Groth16Proof<'a> {
+    /// Parse a proof from its 256-byte serialization
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() != PROOF_LEN {
+            return None;
+        }
+        Some(Self {
+            a: &bytes[..G1_LEN],
+            b: &bytes[G1_LEN..G1_LEN + G2_LEN],
+            c: &bytes[G1_LEN + G2_LEN..],
+        })
+    }
+}
+
+/// A parsed Groth16 verifying key
+pub struct Groth16VerifyingKey<'a> {
+    pub alpha_g1: &'a [u8],
+    pub beta_g2: &'a [u8],
+    pub gamma_g2: &'a [u8],
+    pub delta_g2: &'a [u8],
+    /// `IC[0..=n]`, one G1 point per public input plus the constant term
+    pub ic: &'a [u8],
+}
+
+impl<'a> Groth16VerifyingKey<'a> {
+    /// Parse a verifying key from its serialization
+    pub fn from_bytes(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() < VK_HEADER_LEN + G1_LEN {
+            return None;
+        }
+        let (alpha_g1, rest) = bytes.split_at(G1_LEN);
+        let (beta_g2, rest) = rest.split_at(G2_LEN);
+        let (gamma_g2, rest) = rest.split_at(G2_LEN);
+        let (delta_g2, ic) = rest.split_at(G2_LEN);
+        if !ic.chunks_exact(G1_LEN).remainder().is_empty() {
+            return None;
+        }
+        Some(Self {
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            ic,
+        })
+    }
+
+    /// Number of public inputs the circuit expects
+    pub fn num_public_inputs(&self) -> usize {
+        self.ic.len() / G1_LEN - 1
+    }
+}
+
+/// Verify a serialized Groth16 proof against a serialized verifying key.
+///
+/// Checks `e(-A, B) · e(alpha, beta) · e(vk_x, gamma) · e(C, delta) == 1`
+/// where `vk_x = IC[0] + Σ input[i] · IC[i + 1]`.
+pub fn verify(proof: &[u8], public_inputs: &[[u8; 32]], verifying_key: &[u8]) -> bool {
+    let (Some(proof), Some(vk)) = (
+        Groth16Proof::from_bytes(proof),
+        Groth16VerifyingKey::from_bytes(verifying_key),
+    ) else {
+        return false;
+    };
+
+    if public_inputs.len() != vk.num_public_inputs() {
+        return false;
+    }
+
+    let Some(vk_x) = prepare_inputs(&vk, public_inputs) else {
+        return false;
+    };
+    let Some(neg_a) = negate_g1(proof.a) else {
+        return false;
+    };
+
+    let mut pairing_input = Vec::with_capacity(4 * (G1_LEN + G2_LEN));
+    pairing_input.extend_from_slice(&neg_a);
+    pairing_input.extend_from_slice(proof.b);
+    pairing_input.extend_from_slice(vk.alpha_g1);
+    pairing_input.extend_from_slice(vk.beta_g2);
+    pairing_input.extend_from_slice(&vk_x);
+    pairing_input.extend_from_slice(vk.gamma_g2);
+    pairing_input.extend_from_slice(proof.c);
+    pairing_input.extend_from_slice(vk.delta_g2);
+
+    match alt_bn128_pairing(&pairing_input) {
+        Ok(result) => {
+            result.len() == 32 && result[..31].iter().all(|b| *b == 0) && result[31] == 1
+        }
+        Err(_) => false,
+    }
+}
+
+/// Compute `vk_x = IC[0] + Σ input[i] · IC[i + 1]`
+fn prepare_inputs(vk: &Groth16VerifyingKey, public_inputs: &[[u8; 32]]) -> Option<[u8; G1_LEN]> {
+    let mut acc = [0u8; G1_LEN];
+    acc.copy_from_slice(&vk.ic[..G1_LEN]);
+
+    for (input, ic) in public_inputs.iter().zip(vk.ic[G1_LEN..].chunks_exact(G1_LEN)) {
+        if input.as_slice() >= SCALAR_FIELD_MODULUS.as_slice() {
+            return None;
+        }
+
+        let mut mul_input = [0u8; G1_LEN + 32];
+        mul_input[..G1_LEN].copy_from_slice(ic);
+        mul_input[G1_LEN..].copy_from_slice(input);
+        let term = alt_bn128_multiplication(&mul_input).ok()?;
+
+        let mut add_input = [0u8; 2 * G1_LEN];
+        add_input[..G1_LEN].copy_from_slice(&acc);
+        add_input[G1_LEN..].copy_from_slice(&term);
+        let sum = alt_bn128_addition(&add_input).ok()?;
+        acc.copy_from_slice(&sum);
+    }
+
+    Some(acc)
+}
+
+/// Negate a G1 point: `(x, y) -> (x, q - y)`
+fn negate_g1(point: &[u8]) -> Option<[u8; G1_LEN]> {
+    let mut out = [0u8; G1_LEN];
+    out.copy_from_slice(point);
+
+    // The point at infinity is its own negation
+    if point.iter().all(|b| *b == 0) {
+        return Some(out);
+    }
+
+    let y = &point[32..];
+    if y >= BASE_FIELD_MODULUS.as_slice() {
+        return None;
+    }
+
+    let mut borrow = 0u16;
+    for i in (0..32).rev() {
+        let lhs = BASE_FIELD_MODULUS[i] as u16;
+        let rhs = y[i] as u16 + borrow;
+        if lhs >= rhs {
+            out[32 + i] = (lhs - rhs) as u8;
+            borrow = 0;
+        } else {
+            out[32 + i] = (lhs + 256 - rhs) as u8;
+            borrow = 1;
+        }
+    }
+
+    Some(out)
+}
+
+/// Split a 32-byte value into two public inputs (high and low 16 bytes).
+///
+/// A raw 32-byte hash can exceed the scalar field, so circuits expose such
+/// values as `[Field; 2]` packed big-endian (see `circuits/common`).
+pub fn pack_bytes32(value: &[u8; 32]) -> [[u8; 32]; 2] {
+    let mut hi = [0u8; 32];
+    let mut lo = [0u8; 32];
+    hi[16..].copy_from_slice(&value[..16]);
+    lo[16..].copy_from_slice(&value[16..]);
+    [hi, lo]
+}
+
+/// Encode a `u64` as a single public input
+pub fn u64_input(value: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[24..].copy_from_slice(&value.to_be_bytes());
+    out
+}
+
+/// Encode a `u128` as a single public input
+pub fn u128_input(value: u128) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[16..].copy_from_slice(&value.to_be_bytes());
+    out
+}
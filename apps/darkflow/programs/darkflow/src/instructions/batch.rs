@@ -0,0 +1,456 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, TokenAccount, Transfer};
+use crate::{
+    SettleDarkBatch, DarkOrder, OrderReveal, SwapCurve, SwapDirection, MAX_BATCH_ORDERS, PRICE_SCALE,
+};
+use crate::errors::DarkFlowError;
+use crate::instructions::swap::{calculate_spot_price, calculate_swap_output, close_order_escrow};
+
+/// Accounts passed per order in `remaining_accounts`
+const ACCOUNTS_PER_ORDER: usize = 5;
+
+/// One order of a batch
+struct BatchLeg<'info> {
+    order: Account<'info, DarkOrder>,
+    escrow_token: &'info AccountInfo<'info>,
+    escrow_amount: u64,
+    maker_input_token: &'info AccountInfo<'info>,
+    maker_output_token: &'info AccountInfo<'info>,
+    maker: &'info AccountInfo<'info>,
+}
+
+/// Settle pending dark orders at a single uniform clearing price
+pub fn settle_dark_batch<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SettleDarkBatch<'info>>,
+    reveals: Vec<OrderReveal>,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    // Validate pool is active
+    require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
+
+    // Batches clear against the constant product of the reserves
+    require!(!pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Only active registered solvers may settle
+    let solver = ctx.accounts.solver.key();
+    require!(
+        ctx.accounts.solver_registry.is_active_solver(&solver),
+        DarkFlowError::UnauthorizedSolver
+    );
+
+    let remaining = ctx.remaining_accounts;
+    let order_count = reveals.len();
+    require!(
+        order_count > 0
+            && order_count <= MAX_BATCH_ORDERS
+            && remaining.len() == order_count * ACCOUNTS_PER_ORDER,
+        DarkFlowError::InvalidBatch
+    );
+
+    // Load and validate every order
+    let pool_key = pool.key();
+    let mut legs: Vec<BatchLeg<'info>> = Vec::with_capacity(order_count);
+    for (accounts, reveal) in remaining.chunks_exact(ACCOUNTS_PER_ORDER).zip(reveals.iter()) {
+        let order = Account::<DarkOrder>::try_from(&accounts[0])?;
+        require!(accounts[0].is_writable, DarkFlowError::InvalidBatch);
+        require!(order.pool == pool_key, DarkFlowError::InvalidBatch);
+        require!(order.can_execute()?, DarkFlowError::OrderNotPending);
+        order.verify_reveal(reveal)?;
+        require!(
+            legs.iter().all(|leg| leg.order.key() != order.key()),
+            DarkFlowError::InvalidBatch
+        );
+
+        let (escrow_key, _) = Pubkey::find_program_address(
+            &[b"order_escrow", order.key().as_ref()],
+            &crate::ID,
+        );
+        require!(accounts[1].key() == escrow_key, DarkFlowError::InvalidBatch);
+
+        // Anyone can donate to an escrow, so read its balance; the excess
+        // goes back to the maker
+        let escrow = Account::<TokenAccount>::try_from(&accounts[1])?;
+        require!(
+            escrow.amount >= order.input_amount,
+            DarkFlowError::InsufficientBalance
+        );
+
+        let maker_input = Account::<TokenAccount>::try_from(&accounts[2])?;
+        require!(maker_input.owner == order.maker, DarkFlowError::Unauthorized);
+        require!(
+            maker_input.mint == order.input_mint,
+            DarkFlowError::InvalidTokenMint
+        );
+
+        let maker_output = Account::<TokenAccount>::try_from(&accounts[3])?;
+        require!(maker_output.owner == order.maker, DarkFlowError::Unauthorized);
+        require!(
+            maker_output.mint == order.output_mint,
+            DarkFlowError::InvalidTokenMint
+        );
+        require!(accounts[4].key() == order.maker, DarkFlowError::Unauthorized);
+
+        legs.push(BatchLeg {
+            order,
+            escrow_token: &accounts[1],
+            escrow_amount: escrow.amount,
+            maker_input_token: &accounts[2],
+            maker_output_token: &accounts[3],
+            maker: &accounts[4],
+        });
+    }
+
+    // Advance the TWAP accumulators to now at the reserves the last change left
+    let now = Clock::get()?.unix_timestamp;
+    let observation = pool.accumulate_price(now);
+    ctx.accounts.price_oracle.record(observation);
+
+    let (reserve_a, reserve_b) = pool.reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    // Compute the clearing price and every order's fill
+    let inputs: Vec<(bool, u64)> = legs
+        .iter()
+        .map(|leg| (leg.order.direction == SwapDirection::AToB, leg.order.input_amount))
+        .collect();
+    let (price, outputs) = clear_batch(
+        &inputs,
+        reserve_a,
+        reserve_b,
+        pool.fee_rate_bps,
+        pool.curve,
+        pool.amplification(now),
+    )?;
+
+    for (output, reveal) in outputs.iter().zip(reveals.iter()) {
+        require!(*output >= reveal.min_output, DarkFlowError::SlippageExceeded);
+    }
+
+    let pool_seeds = &[
+        b"dark_pool",
+        pool.token_a_mint.as_ref(),
+        pool.token_b_mint.as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&pool_seeds[..]];
+
+    // Move every escrowed input into the pool before paying anyone out
+    for leg in legs.iter() {
+        let vault_input = match leg.order.direction {
+            SwapDirection::AToB => &ctx.accounts.vault_a,
+            SwapDirection::BToA => &ctx.accounts.vault_b,
+        };
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: leg.escrow_token.clone(),
+                to: vault_input.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(transfer_ctx, leg.order.input_amount)?;
+
+        close_order_escrow(
+            ctx.accounts.token_program.to_account_info(),
+            pool.to_account_info(),
+            signer,
+            leg.escrow_token.clone(),
+            leg.escrow_amount - leg.order.input_amount,
+            leg.maker_input_token.clone(),
+            leg.maker.clone(),
+        )?;
+    }
+
+    // Pay every maker at the clearing price
+    let mut volume_a = 0u64;
+    let mut volume_b = 0u64;
+    let mut net_input_a = 0i128;
+    let mut net_input_b = 0i128;
+    for (leg, output) in legs.iter_mut().zip(outputs.iter()) {
+        let vault_output = match leg.order.direction {
+            SwapDirection::AToB => &ctx.accounts.vault_b,
+            SwapDirection::BToA => &ctx.accounts.vault_a,
+        };
+
+        if *output > 0 {
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: vault_output.to_account_info(),
+                    to: leg.maker_output_token.clone(),
+                    authority: pool.to_account_info(),
+                },
+                signer,
+            );
+            token::transfer(transfer_ctx, *output)?;
+        }
+
+        let (amount_a, amount_b) = match leg.order.direction {
+            SwapDirection::AToB => {
+                net_input_a += leg.order.input_amount as i128;
+                net_input_b -= *output as i128;
+                (leg.order.input_amount, *output)
+            }
+            SwapDirection::BToA => {
+                net_input_b += leg.order.input_amount as i128;
+                net_input_a -= *output as i128;
+                (*output, leg.order.input_amount)
+            }
+        };
+        volume_a = volume_a.saturating_add(amount_a);
+        volume_b = volume_b.saturating_add(amount_b);
+
+        leg.order.fill(solver, Vec::new())?;
+        leg.order.exit(&crate::ID)?;
+        ctx.accounts.solver_registry.record_fill(&solver)?;
+    }
+
+    // Only the residual the pool absorbed paid the swap fee
+    if net_input_a > 0 {
+        pool.accrue_swap_fee(net_input_a as u64, true)?;
+    }
+    if net_input_b > 0 {
+        pool.accrue_swap_fee(net_input_b as u64, false)?;
+    }
+    pool.add_volume(volume_a, volume_b)?;
+
+    // Price the TWAP at the reserves this batch leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    msg!("Dark batch settled");
+    msg!("Orders: {}", order_count);
+    msg!("Clearing price (B per A, 1e12): {}", price);
+    msg!("Solver: {}", solver);
+
+    Ok(())
+}
+
+// Helper functions
+
+/// Find the uniform clearing price of a batch and each order's output
+///
+/// `inputs` holds `(a_to_b, input_amount)` per order. Let X be the side
+/// whose input is worth more at the pool's spot price. At a price `p`
+/// (Y per X), X sellers receive `x * p` and Y sellers `y / p`; the pool
+/// absorbs the net imbalance. The clearing price is the highest `p` at
+/// which the pool can supply the Y owed to X sellers by swapping the
+/// residual X along the pool's curve, i.e. the residual trades at exactly
+/// the AMM's execution price and every order in the batch gets that price.
+///
+/// Returns the price as token B per token A scaled by `PRICE_SCALE`.
+fn clear_batch(
+    inputs: &[(bool, u64)],
+    reserve_a: u64,
+    reserve_b: u64,
+    fee_bps: u16,
+    curve: SwapCurve,
+    amplification: u64,
+) -> Result<(u128, Vec<u64>)> {
+    require!(reserve_a > 0 && reserve_b > 0, DarkFlowError::InsufficientBalance);
+
+    let sum = |a_to_b: bool| -> u128 {
+        inputs
+            .iter()
+            .filter(|(direction, _)| *direction == a_to_b)
+            .map(|(_, amount)| *amount as u128)
+            .sum()
+    };
+    let (sum_a, sum_b) = (sum(true), sum(false));
+
+    // Orient so that X is the side with excess value at spot
+    let value_a = sum_a
+        .checked_mul(calculate_spot_price(reserve_a, reserve_b, curve, amplification)?)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    let value_b = sum_b
+        .checked_mul(PRICE_SCALE)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    let x_is_a = value_a >= value_b;
+    let (reserve_x, reserve_y) = if x_is_a {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+
+    // The clearing price never exceeds spot for the excess side
+    let spot = calculate_spot_price(reserve_x, reserve_y, curve, amplification)?;
+
+    // Output of swapping `amount_in` through the pool, as a signed amount
+    let swap = |amount_in: i128, reserve_in: u64, reserve_out: u64| -> Result<i128> {
+        let amount_in = u64::try_from(amount_in).map_err(|_| DarkFlowError::MathOverflow)?;
+        Ok(calculate_swap_output(amount_in, reserve_in, reserve_out, fee_bps, curve, amplification)? as i128)
+    };
+
+    // Binary search the highest price at which X sellers are not overpaid
+    let (mut lo, mut hi) = (0u128, spot);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        let (_, dx, dy) = batch_fills(inputs, x_is_a, mid)?;
+        let x_not_overpaid = dy <= 0 || (dx > 0 && dy <= swap(dx, reserve_x, reserve_y)?);
+        if x_not_overpaid {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    require!(lo > 0, DarkFlowError::BatchNotClearable);
+
+    // Rounding can leave a dust imbalance; the pool must be able to absorb it
+    let (outputs, dx, dy) = batch_fills(inputs, x_is_a, lo)?;
+    let feasible = match (dx.signum(), dy.signum()) {
+        (_, 1) => dx > 0 && dy <= swap(dx, reserve_x, reserve_y)?,
+        (-1, _) => -dx <= swap(-dy, reserve_y, reserve_x)?,
+        _ => true,
+    };
+    require!(feasible, DarkFlowError::BatchNotClearable);
+
+    let price_b_per_a = if x_is_a {
+        lo
+    } else {
+        PRICE_SCALE * PRICE_SCALE / lo
+    };
+
+    Ok((price_b_per_a, outputs))
+}
+
+/// Outputs of every order at `price` (Y per X, scaled), and the pool's net
+/// X inflow `dx` and Y outflow `dy`
+fn batch_fills(inputs: &[(bool, u64)], x_is_a: bool, price: u128) -> Result<(Vec<u64>, i128, i128)> {
+    let mut outputs = Vec::with_capacity(inputs.len());
+    let mut dx = 0i128;
+    let mut dy = 0i128;
+
+    for (a_to_b, amount) in inputs.iter() {
+        let amount = *amount as u128;
+        let output = if *a_to_b == x_is_a {
+            // X seller receives Y
+            let output = amount.checked_mul(price).ok_or(DarkFlowError::MathOverflow)? / PRICE_SCALE;
+            dx += amount as i128;
+            dy += output as i128;
+            output
+        } else {
+            // Y seller receives X
+            let output = amount.checked_mul(PRICE_SCALE).ok_or(DarkFlowError::MathOverflow)? / price;
+            dx -= output as i128;
+            dy -= amount as i128;
+            output
+        };
+        outputs.push(u64::try_from(output).map_err(|_| DarkFlowError::MathOverflow)?);
+    }
+
+    Ok((outputs, dx, dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESERVE: u64 = 1_000_000_000;
+    const FEE_BPS: u16 = 30;
+
+    fn clear(inputs: &[(bool, u64)], reserve_a: u64, reserve_b: u64) -> Result<(u128, Vec<u64>)> {
+        clear_batch(inputs, reserve_a, reserve_b, FEE_BPS, SwapCurve::ConstantProduct, 0)
+    }
+
+    /// Output of one swap of `amount` through a balanced pool
+    fn swap_out(amount: u64) -> u64 {
+        calculate_swap_output(amount, RESERVE, RESERVE, FEE_BPS, SwapCurve::ConstantProduct, 0).unwrap()
+    }
+
+    #[test]
+    fn batch_fills_pay_each_side_at_the_price() {
+        // A sellers get 2 B per A, B sellers 0.5 A per B
+        let inputs = [(true, 1_000), (false, 500), (true, 3)];
+        let (outputs, dx, dy) = batch_fills(&inputs, true, 2 * PRICE_SCALE).unwrap();
+        assert_eq!(outputs, vec![2_000, 250, 6]);
+        assert_eq!((dx, dy), (1_003 - 250, 2_006 - 500));
+
+        // Oriented the other way, the price is A per B
+        let (outputs, dx, dy) = batch_fills(&inputs, false, PRICE_SCALE / 2).unwrap();
+        assert_eq!(outputs, vec![2_000, 250, 6]);
+        assert_eq!((dx, dy), (500 - 2_006, 250 - 1_003));
+    }
+
+    #[test]
+    fn one_sided_batch_clears_at_the_pool_execution_price() {
+        let inputs = [(true, 1_000_000), (true, 3_000_000), (true, 7)];
+        let total = inputs.iter().map(|(_, amount)| amount).sum::<u64>();
+        let (price, outputs) = clear(&inputs, RESERVE, RESERVE).unwrap();
+
+        // Below spot, and together the orders get what one swap of the total would
+        assert!(price < PRICE_SCALE);
+        let swapped = swap_out(total);
+        let paid = outputs.iter().sum::<u64>();
+        assert!(paid <= swapped);
+        assert!(swapped - paid <= inputs.len() as u64, "paid {} of {}", paid, swapped);
+
+        // Every order gets the same price
+        for ((_, amount), output) in inputs.iter().zip(outputs.iter()) {
+            assert_eq!(*output as u128, *amount as u128 * price / PRICE_SCALE);
+        }
+    }
+
+    #[test]
+    fn balanced_batch_clears_at_spot_without_touching_the_pool() {
+        let inputs = [(true, 5_000_000), (false, 2_000_000), (false, 3_000_000)];
+        let (price, outputs) = clear(&inputs, RESERVE, RESERVE).unwrap();
+
+        assert_eq!(price, PRICE_SCALE);
+        assert_eq!(outputs, vec![5_000_000, 2_000_000, 3_000_000]);
+
+        // Balanced at a 2:1 spot as well, from either orientation
+        let inputs = [(true, 1_000_000), (false, 2_000_000)];
+        let (price, outputs) = clear(&inputs, RESERVE, 2 * RESERVE).unwrap();
+        assert_eq!(price, 2 * PRICE_SCALE);
+        assert_eq!(outputs, vec![2_000_000, 1_000_000]);
+    }
+
+    #[test]
+    fn dust_imbalance_is_absorbed_by_the_pool() {
+        for inputs in [
+            [(true, 1_000_001), (false, 1_000_000)],
+            [(true, 1_000_000), (false, 1_000_003)],
+        ] {
+            let (price, outputs) = clear(&inputs, RESERVE, RESERVE).unwrap();
+            let (_, dx_a, dy_b) = batch_fills(&inputs, true, price).unwrap();
+
+            // The excess side takes a price at most spot, and the pool pays
+            // out no more than swapping the residual would
+            if inputs[0].1 > inputs[1].1 {
+                assert!(price <= PRICE_SCALE);
+                assert!(dy_b <= 0 || dy_b as u64 <= swap_out(dx_a as u64));
+            } else {
+                assert!(price >= PRICE_SCALE);
+                assert!(dx_a >= 0 || (-dx_a) as u64 <= swap_out((-dy_b) as u64));
+            }
+            assert!(outputs.iter().all(|output| (999_990..=1_000_003).contains(output)), "{:?}", outputs);
+        }
+    }
+
+    #[test]
+    fn stable_swap_batch_clears_near_parity() {
+        let inputs = [(true, 10_000_000), (false, 4_000_000)];
+        let (price, outputs) = clear_batch(&inputs, RESERVE, RESERVE, FEE_BPS, SwapCurve::StableSwap, 100).unwrap();
+
+        // The residual barely moves a stable pool, so the price is about
+        // parity less the fee
+        assert!(price < PRICE_SCALE && price > PRICE_SCALE * 996 / 1_000, "{}", price);
+        assert!(outputs[1] >= 4_000_000);
+    }
+
+    #[test]
+    fn batch_the_pool_cannot_fill_is_not_clearable() {
+        // No positive price leaves the pool able to pay the excess side
+        let inputs = [(true, 2 * PRICE_SCALE as u64)];
+        assert_eq!(
+            clear(&inputs, 1_000_000, 1).unwrap_err(),
+            DarkFlowError::BatchNotClearable.into()
+        );
+
+        assert!(clear(&inputs, 0, RESERVE).is_err());
+    }
+}
@@ -1,48 +1,81 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Transfer};
-use crate::{LaunchConfidentialToken, BuyFromLaunch, LaunchStatus};
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::{
+    LaunchConfidentialToken, BuyFromLaunch, ClaimLaunchTokens, EndLaunch, CancelLaunch,
+    RefundLaunchPurchase, WithdrawLaunchProceeds, LaunchStatus, LaunchCaps, CurveParams, CurveType,
+    ConfidentialLaunch, PurchaseReveal, ENCRYPTED_AMOUNT_LEN,
+};
 use crate::errors::DarkFlowError;
 
-/// Launch a token with confidential bonding curve
+/// Launch a token on a public bonding curve with confidential purchases
+#[allow(clippy::too_many_arguments)]
 pub fn launch_confidential_token(
     ctx: Context<LaunchConfidentialToken>,
     token_mint: Pubkey,
-    encrypted_curve_params: Vec<u8>,
+    curve: CurveParams,
     initial_price: u64,
     max_supply: u64,
+    start_time: i64,
+    end_time: i64,
+    caps: LaunchCaps,
 ) -> Result<()> {
     // Validate parameters
     require!(initial_price > 0, DarkFlowError::InvalidAmount);
     require!(max_supply > 0, DarkFlowError::InvalidAmount);
+    curve.validate(max_supply)?;
+    caps.validate()?;
     require!(
-        encrypted_curve_params.len() > 0 && encrypted_curve_params.len() <= 256,
-        DarkFlowError::InvalidEncryptedData
+        token_mint == ctx.accounts.token_mint.key(),
+        DarkFlowError::InvalidTokenMint
     );
 
     let launch = &mut ctx.accounts.launch;
     let now = Clock::get()?.unix_timestamp;
 
+    // 0 = start immediately / no end
+    let start_time = if start_time == 0 { now } else { start_time };
+    require!(
+        end_time == 0 || (end_time > start_time && end_time > now),
+        DarkFlowError::InvalidLaunchSchedule
+    );
+
     launch.creator = ctx.accounts.creator.key();
     launch.token_mint = token_mint;
-    launch.payment_mint = Pubkey::default(); // Set based on config
-    launch.encrypted_curve_params = encrypted_curve_params;
+    launch.payment_mint = ctx.accounts.payment_mint.key();
+    launch.curve = curve;
     launch.initial_price = initial_price;
     launch.max_supply = max_supply;
-    launch.encrypted_sold = Vec::new();
-    launch.approximate_sold = 0;
     launch.buyer_count = 0;
     launch.total_payments = 0;
+    launch.caps = caps;
     launch.status = LaunchStatus::Active;
-    launch.start_time = now;
-    launch.end_time = 0; // No end time by default
+    launch.start_time = start_time;
+    launch.end_time = end_time;
     launch.state_commitment = [0u8; 32];
     launch.created_at = now;
-    launch.bump = 0; // No PDA derivation, using default
+    launch.bump = ctx.bumps.launch;
+    launch.token_vault_bump = ctx.bumps.token_vault;
+    launch.payment_vault_bump = ctx.bumps.launch_vault;
+    launch.proceeds_withdrawn = false;
+
+    // Fund the program-owned vault with the full supply for sale
+    let transfer_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.creator_token.to_account_info(),
+            to: ctx.accounts.token_vault.to_account_info(),
+            authority: ctx.accounts.creator.to_account_info(),
+        },
+    );
+    token::transfer(transfer_ctx, max_supply)?;
 
     msg!("Confidential token launch created");
     msg!("Token: {}", token_mint);
     msg!("Initial price: {}", initial_price);
     msg!("Max supply: {}", max_supply);
+    msg!("Window: {} - {}", start_time, end_time);
+    msg!("Soft cap: {}, hard cap: {}", caps.soft_cap, caps.hard_cap);
+    msg!("Curve: {:?} ({}, {})", curve.curve_type, curve.param_a, curve.param_b);
 
     Ok(())
 }
@@ -51,7 +84,8 @@ pub fn launch_confidential_token(
 pub fn buy_from_launch(
     ctx: Context<BuyFromLaunch>,
     encrypted_amount: Vec<u8>,
-    commitment: [u8; 32],
+    totals: PurchaseReveal,
+    salt: [u8; 32],
     payment_amount: u64,
 ) -> Result<()> {
     let launch = &mut ctx.accounts.launch;
@@ -62,29 +96,41 @@ pub fn buy_from_launch(
     // Validate payment
     require!(payment_amount > 0, DarkFlowError::InvalidAmount);
 
-    // Validate encrypted amount
+    // Validate encrypted amount (lo/hi ElGamal ciphertexts)
     require!(
-        encrypted_amount.len() > 0 && encrypted_amount.len() <= 128,
+        encrypted_amount.len() == ENCRYPTED_AMOUNT_LEN,
         DarkFlowError::InvalidEncryptedData
     );
 
-    // Validate commitment
-    require!(commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
+    // Validate the new blinding salt and the reveal of the previous totals
+    require!(salt != [0u8; 32], DarkFlowError::InvalidCommitment);
+    let purchase_key = ctx.accounts.purchase.key();
+    ctx.accounts.purchase.verify_reveal(&purchase_key, &totals)?;
 
-    // Calculate tokens to receive based on bonding curve
-    // In production, this would use the encrypted curve params via MPC
-    let tokens_to_receive = calculate_tokens_for_payment(
-        payment_amount,
+    // Check not sold out
+    let sold = sold_supply(launch)?;
+    require!(sold < launch.max_supply, DarkFlowError::LaunchSoldOut);
+
+    // Clamp the spend to the hard cap and this wallet's remaining allowance.
+    // The allowance is checked against the buyer's revealed totals; only the
+    // aggregate raise is published.
+    let remaining_raise = launch.remaining_raise();
+    require!(remaining_raise > 0, DarkFlowError::LaunchSoldOut);
+    let allowance = launch.caps.remaining_allowance(totals.payment_amount);
+    require!(allowance > 0, DarkFlowError::WalletLimitExceeded);
+    let budget = payment_amount.min(remaining_raise).min(allowance);
+
+    // Integrate the bonding curve from the position the raise implies
+    let (tokens_to_receive, cost) = price_purchase(
+        launch.total_payments,
+        budget,
         launch.initial_price,
-        launch.approximate_sold,
+        &launch.curve,
         launch.max_supply,
     )?;
-
-    // Check not sold out
-    require!(
-        launch.approximate_sold + tokens_to_receive <= launch.max_supply,
-        DarkFlowError::LaunchSoldOut
-    );
+    require!(tokens_to_receive > 0, DarkFlowError::InvalidAmount);
+    require!(cost <= budget, DarkFlowError::SlippageExceeded);
+    let sold_after = sold + tokens_to_receive;
 
     // Transfer payment
     let transfer_ctx = CpiContext::new(
@@ -95,52 +141,562 @@ pub fn buy_from_launch(
             authority: ctx.accounts.buyer.to_account_info(),
         },
     );
-    token::transfer(transfer_ctx, payment_amount)?;
+    token::transfer(transfer_ctx, cost)?;
 
-    // Update encrypted sold amount
-    let encrypted_sold_update = update_encrypted_sold(
-        &launch.encrypted_sold,
-        &encrypted_amount,
-    );
+    // Record the purchase on the buyer's claim account
+    let purchase = &mut ctx.accounts.purchase;
+    let new_buyer = purchase.is_new();
+    if new_buyer {
+        purchase.buyer = ctx.accounts.buyer.key();
+        purchase.launch = launch.key();
+        purchase.bump = ctx.bumps.purchase;
+    }
+    purchase.record(&purchase_key, &encrypted_amount, &totals, salt, cost, tokens_to_receive)?;
 
     // Record purchase
-    launch.record_purchase(payment_amount, encrypted_sold_update);
+    launch.record_purchase(cost, new_buyer)?;
 
-    // Check if sold out
-    if launch.approximate_sold >= launch.max_supply {
+    // Check if sold out (supply exhausted or hard cap reached)
+    if sold_after >= launch.max_supply || launch.remaining_raise() == 0 {
         launch.mark_sold_out();
     }
 
     msg!("Confidential purchase completed");
-    msg!("Payment: {}", payment_amount);
+    msg!("Total raised: {}", launch.total_payments);
+    msg!("Supply sold: {}", sold_after);
     msg!("Buyers: {}", launch.buyer_count);
 
     Ok(())
 }
 
+/// Claim purchased tokens from the launch vault after the sale ends
+pub fn claim_launch_tokens(ctx: Context<ClaimLaunchTokens>, totals: PurchaseReveal) -> Result<()> {
+    let launch = &ctx.accounts.launch;
+    let purchase_key = ctx.accounts.purchase.key();
+    let purchase = &mut ctx.accounts.purchase;
+
+    require!(launch.is_sale_over()?, DarkFlowError::LaunchNotEnded);
+    require!(launch.soft_cap_met(), DarkFlowError::SoftCapNotMet);
+    require!(!purchase.claimed, DarkFlowError::AlreadyClaimed);
+    purchase.verify_reveal(&purchase_key, &totals)?;
+    require!(totals.token_amount > 0, DarkFlowError::InvalidAmount);
+
+    transfer_from_launch_vault(
+        &ctx.accounts.token_program,
+        launch,
+        &ctx.accounts.token_vault,
+        ctx.accounts.buyer_receive_token.to_account_info(),
+        totals.token_amount,
+    )?;
+
+    purchase.claimed = true;
+
+    msg!("Launch tokens claimed");
+
+    Ok(())
+}
+
+/// End an active launch early (creator only)
+pub fn end_launch(ctx: Context<EndLaunch>) -> Result<()> {
+    let launch = &mut ctx.accounts.launch;
+
+    require!(launch.status == LaunchStatus::Active, DarkFlowError::LaunchNotActive);
+
+    launch.end();
+
+    msg!("Launch ended");
+    msg!("Buyers: {}", launch.buyer_count);
+
+    Ok(())
+}
+
+/// Cancel a launch before the sale is over (creator only)
+///
+/// All tokens for sale are returned to the creator; buyers recover their
+/// payments with `refund_launch_purchase`.
+pub fn cancel_launch(ctx: Context<CancelLaunch>) -> Result<()> {
+    let launch = &mut ctx.accounts.launch;
+
+    require!(launch.status == LaunchStatus::Active, DarkFlowError::LaunchNotActive);
+    require!(!launch.is_sale_over()?, DarkFlowError::LaunchNotActive);
+
+    launch.cancel();
+
+    transfer_from_launch_vault(
+        &ctx.accounts.token_program,
+        launch,
+        &ctx.accounts.token_vault,
+        ctx.accounts.creator_token.to_account_info(),
+        ctx.accounts.token_vault.amount,
+    )?;
+
+    msg!("Launch cancelled");
+
+    Ok(())
+}
+
+/// Refund a buyer's payments from a cancelled launch or one that ended
+/// under its soft cap
+pub fn refund_launch_purchase(ctx: Context<RefundLaunchPurchase>, totals: PurchaseReveal) -> Result<()> {
+    let launch = &ctx.accounts.launch;
+    let purchase = &ctx.accounts.purchase;
+
+    require!(launch.is_refundable()?, DarkFlowError::LaunchNotRefundable);
+    require!(!purchase.claimed, DarkFlowError::AlreadyClaimed);
+    purchase.verify_reveal(&purchase.key(), &totals)?;
+
+    transfer_from_launch_vault(
+        &ctx.accounts.token_program,
+        launch,
+        &ctx.accounts.launch_vault,
+        ctx.accounts.buyer_payment_token.to_account_info(),
+        totals.payment_amount,
+    )?;
+
+    msg!("Launch purchase refunded");
+
+    Ok(())
+}
+
+/// Withdraw payments and unsold tokens once the sale is over (creator only)
+///
+/// If the raise missed the soft cap, payments stay in the vault for buyer
+/// refunds and the creator recovers every token for sale instead.
+pub fn withdraw_launch_proceeds(ctx: Context<WithdrawLaunchProceeds>) -> Result<()> {
+    let launch = &mut ctx.accounts.launch;
+
+    require!(launch.is_sale_over()?, DarkFlowError::LaunchNotEnded);
+    require!(!launch.proceeds_withdrawn, DarkFlowError::ProceedsAlreadyWithdrawn);
+
+    // A launch past its end time is ended for good
+    if launch.status == LaunchStatus::Active {
+        launch.end();
+    }
+    launch.proceeds_withdrawn = true;
+
+    let soft_cap_met = launch.soft_cap_met();
+    let (payments, tokens) = if soft_cap_met {
+        (
+            launch.total_payments,
+            launch.max_supply.saturating_sub(sold_supply(launch)?),
+        )
+    } else {
+        (0, ctx.accounts.token_vault.amount)
+    };
+
+    if payments > 0 {
+        transfer_from_launch_vault(
+            &ctx.accounts.token_program,
+            launch,
+            &ctx.accounts.launch_vault,
+            ctx.accounts.creator_payment_token.to_account_info(),
+            payments,
+        )?;
+    }
+
+    if tokens > 0 {
+        transfer_from_launch_vault(
+            &ctx.accounts.token_program,
+            launch,
+            &ctx.accounts.token_vault,
+            ctx.accounts.creator_token.to_account_info(),
+            tokens,
+        )?;
+    }
+
+    msg!("Launch proceeds withdrawn");
+    msg!("Soft cap met: {}", soft_cap_met);
+    msg!("Payments: {}", payments);
+    msg!("Tokens returned: {}", tokens);
+
+    Ok(())
+}
+
 // Helper functions
 
+/// Transfer out of a launch vault, signed by the launch PDA
+fn transfer_from_launch_vault<'info>(
+    token_program: &Program<'info, Token>,
+    launch: &Account<'info, ConfidentialLaunch>,
+    vault: &Account<'info, TokenAccount>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"launch".as_ref(),
+        launch.creator.as_ref(),
+        launch.token_mint.as_ref(),
+        &[launch.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        Transfer {
+            from: vault.to_account_info(),
+            to,
+            authority: launch.to_account_info(),
+        },
+        signer,
+    );
+    token::transfer(transfer_ctx, amount)
+}
+
+/// Supply sold so far
+///
+/// Purchases keep `total_payments` equal to the exact curve cost of the
+/// supply sold, so the sale position is the most supply the raise pays
+/// for.
+fn sold_supply(launch: &ConfidentialLaunch) -> Result<u64> {
+    calculate_tokens_for_payment(
+        launch.total_payments,
+        launch.initial_price,
+        &launch.curve,
+        0,
+        launch.max_supply,
+    )
+}
+
+/// Tokens a purchase receives and its cost, given the raise so far
+///
+/// The new position is the supply the raise plus `budget` pays for. The
+/// cost is the curve cost of that position less the raise, so the raise
+/// stays the exact cost of the supply sold and never exceeds `budget`.
+fn price_purchase(
+    raise: u64,
+    budget: u64,
+    initial_price: u64,
+    curve: &CurveParams,
+    max_supply: u64,
+) -> Result<(u64, u64)> {
+    let sold = calculate_tokens_for_payment(raise, initial_price, curve, 0, max_supply)?;
+    let raise_with_budget = raise.checked_add(budget).ok_or(DarkFlowError::MathOverflow)?;
+    let sold_after = calculate_tokens_for_payment(raise_with_budget, initial_price, curve, 0, max_supply)?;
+
+    let cost = calculate_cost_for_tokens(sold_after, initial_price, curve, 0)?
+        .checked_sub(raise)
+        .ok_or(DarkFlowError::MathOverflow)?;
+
+    Ok((sold_after - sold, cost))
+}
+
+/// Tokens bought by spending at most `payment`, starting at `current_sold`
+///
+/// The result is capped at the remaining supply, and its cost from
+/// `calculate_cost_for_tokens` never exceeds `payment`.
 fn calculate_tokens_for_payment(
     payment: u64,
     initial_price: u64,
-    _current_sold: u64,
-    _max_supply: u64,
+    curve: &CurveParams,
+    current_sold: u64,
+    max_supply: u64,
 ) -> Result<u64> {
-    // Simple linear bonding curve: price increases with supply
-    // price = initial_price * (1 + sold / max_supply)
-    // For simplicity, use initial price for now
+    let remaining = max_supply.saturating_sub(current_sold);
 
-    let tokens = payment
-        .checked_div(initial_price.max(1))
-        .ok_or(DarkFlowError::MathOverflow)?;
+    let tokens = match curve.curve_type {
+        CurveType::Linear | CurveType::ConstantProduct => {
+            // Cost is monotonic in the token count: binary search the largest
+            // amount whose integral fits the payment
+            let (mut lo, mut hi) = (0u64, remaining);
+            while lo < hi {
+                let mid = lo + (hi - lo).div_ceil(2);
+                match integral_cost(initial_price, curve, current_sold, mid) {
+                    Some(cost) if cost <= payment as u128 => lo = mid,
+                    _ => hi = mid - 1,
+                }
+            }
+            lo
+        }
+        CurveType::Exponential | CurveType::Stepped => {
+            walk_steps(initial_price, curve, current_sold, remaining, payment as u128)
+                .ok_or(DarkFlowError::MathOverflow)?
+                .0
+        }
+    };
+
+    Ok(tokens.min(remaining))
+}
+
+/// Payment required to buy `tokens`, starting at `current_sold`
+fn calculate_cost_for_tokens(
+    tokens: u64,
+    initial_price: u64,
+    curve: &CurveParams,
+    current_sold: u64,
+) -> Result<u64> {
+    let cost = match curve.curve_type {
+        CurveType::Linear | CurveType::ConstantProduct => {
+            integral_cost(initial_price, curve, current_sold, tokens)
+        }
+        CurveType::Exponential | CurveType::Stepped => {
+            walk_steps(initial_price, curve, current_sold, tokens, u128::MAX)
+                .filter(|(bought, _)| *bought == tokens)
+                .map(|(_, cost)| cost)
+        }
+    };
+
+    cost.and_then(|cost| u64::try_from(cost).ok())
+        .ok_or(DarkFlowError::MathOverflow.into())
+}
+
+/// Cost of `tokens` from `sold` on a curve with a closed-form integral
+fn integral_cost(initial_price: u64, curve: &CurveParams, sold: u64, tokens: u64) -> Option<u128> {
+    match curve.curve_type {
+        CurveType::Linear => linear_cost(initial_price, curve, sold, tokens),
+        CurveType::ConstantProduct => constant_product_cost(curve, sold, tokens),
+        CurveType::Exponential | CurveType::Stepped => None,
+    }
+}
+
+/// Integral of `price(x) = initial_price + x * param_a / param_b` over
+/// `[sold, sold + tokens)`, rounded up
+fn linear_cost(initial_price: u64, curve: &CurveParams, sold: u64, tokens: u64) -> Option<u128> {
+    let tokens = tokens as u128;
+    let base = (initial_price as u128).checked_mul(tokens)?;
+    // a * ((s + n)^2 - s^2) / 2b = a * n * (2s + n) / 2b
+    let span = (2 * sold as u128).checked_add(tokens)?;
+    let slope = (curve.param_a as u128)
+        .checked_mul(tokens)?
+        .checked_mul(span)?
+        .div_ceil(2 * curve.param_b as u128);
+    base.checked_add(slope)
+}
+
+/// Payment reserve growth for buying `tokens` from the virtual pool
+///
+/// R(t) = ceil(k / t) is the payment reserve when t tokens remain in the
+/// virtual pool, so buying n tokens costs R(T - n) - R(T).
+fn constant_product_cost(curve: &CurveParams, sold: u64, tokens: u64) -> Option<u128> {
+    let k = curve.param_a as u128 * curve.param_b as u128;
+    let token_reserve = (curve.param_b as u128).checked_sub(sold as u128)?;
+    let new_token_reserve = token_reserve
+        .checked_sub(tokens as u128)
+        .filter(|reserve| *reserve > 0)?;
+    Some(k.div_ceil(new_token_reserve) - k.div_ceil(token_reserve))
+}
+
+/// Price of step `step` on a stepped or exponential curve
+fn step_price(initial_price: u64, curve: &CurveParams, step: u128) -> u128 {
+    match curve.curve_type {
+        CurveType::Exponential => {
+            let mut price = initial_price as u128;
+            for _ in 0..step {
+                price = next_step_price(curve, price);
+            }
+            price
+        }
+        _ => (curve.param_a as u128)
+            .saturating_mul(step)
+            .saturating_add(initial_price as u128),
+    }
+}
+
+/// Price of the step after one priced at `price`
+fn next_step_price(curve: &CurveParams, price: u128) -> u128 {
+    match curve.curve_type {
+        // Round up so small prices still grow every step
+        CurveType::Exponential => price.saturating_mul(10_000 + curve.param_a as u128).div_ceil(10_000),
+        _ => price.saturating_add(curve.param_a as u128),
+    }
+}
+
+/// Buy up to `max_tokens` across price steps from `sold` without spending
+/// more than `budget`
+///
+/// Returns `(tokens, cost)`. The price is advanced one step at a time, and
+/// validation bounds the number of steps by `MAX_CURVE_STEPS`.
+fn walk_steps(
+    initial_price: u64,
+    curve: &CurveParams,
+    sold: u64,
+    max_tokens: u64,
+    budget: u128,
+) -> Option<(u64, u128)> {
+    let step_size = curve.param_b as u128;
+    let mut position = sold as u128;
+    let mut step = position / step_size;
+    let mut price = step_price(initial_price, curve, step);
+    let mut tokens = 0u128;
+    let mut cost = 0u128;
+
+    while tokens < max_tokens as u128 {
+        let available = ((step + 1) * step_size - position).min(max_tokens as u128 - tokens);
+        let affordable = (budget - cost) / price;
+        let take = available.min(affordable);
+
+        tokens += take;
+        cost = cost.checked_add(take.checked_mul(price)?)?;
+        position += take;
+
+        if take < available {
+            break;
+        }
+
+        step += 1;
+        price = next_step_price(curve, price);
+    }
 
-    Ok(tokens)
+    Some((u64::try_from(tokens).ok()?, cost))
 }
 
-fn update_encrypted_sold(current: &[u8], addition: &[u8]) -> Vec<u8> {
-    // Placeholder: homomorphically add encrypted values
-    // In production, use actual FHE or MPC
-    let mut result = current.to_vec();
-    result.extend_from_slice(addition);
-    result
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_CURVE_STEPS;
+
+    fn curve(curve_type: CurveType, param_a: u64, param_b: u64) -> CurveParams {
+        CurveParams { curve_type, param_a, param_b }
+    }
+
+    /// `tokens` is the most `payment` buys: it is affordable and one more is not
+    fn assert_max_affordable(payment: u64, initial_price: u64, curve: &CurveParams, sold: u64, max_supply: u64) -> u64 {
+        let tokens = calculate_tokens_for_payment(payment, initial_price, curve, sold, max_supply).unwrap();
+        let cost = calculate_cost_for_tokens(tokens, initial_price, curve, sold).unwrap();
+        assert!(cost <= payment, "{:?}: {} tokens cost {} > {}", curve.curve_type, tokens, cost, payment);
+        if sold + tokens < max_supply {
+            let next = calculate_cost_for_tokens(tokens + 1, initial_price, curve, sold);
+            assert!(next.map_or(true, |next| next > payment), "{:?}: {} tokens affordable", curve.curve_type, tokens + 1);
+        }
+        tokens
+    }
+
+    #[test]
+    fn linear_cost_is_the_rounded_up_integral() {
+        let linear = curve(CurveType::Linear, 1, 10);
+
+        // 10 * 100 + (1 / 10) * (100^2 - 0) / 2 = 1000 + 500
+        assert_eq!(calculate_cost_for_tokens(100, 10, &linear, 0).unwrap(), 1_500);
+        // From 100 sold: 10 * 50 + (150^2 - 100^2) / 20 = 500 + 625
+        assert_eq!(calculate_cost_for_tokens(50, 10, &linear, 100).unwrap(), 1_125);
+        // 1 * 3 + (3^2) / 20 = 3.45, rounded up
+        assert_eq!(calculate_cost_for_tokens(3, 1, &linear, 0).unwrap(), 4);
+
+        for payment in [1, 7, 1_000, 123_456, 10_000_000] {
+            assert_max_affordable(payment, 10, &linear, 250, 100_000);
+        }
+    }
+
+    #[test]
+    fn exponential_steps_compound_incrementally() {
+        let exponential = curve(CurveType::Exponential, 100, 1_000);
+
+        // Walking step by step matches pricing each step from scratch
+        let mut price = 1_000u128;
+        for step in 0..MAX_CURVE_STEPS as u128 {
+            assert_eq!(step_price(1_000, &exponential, step), price);
+            price = next_step_price(&exponential, price);
+        }
+
+        // 1000 tokens at 1000, then 500 at 1010
+        assert_eq!(calculate_cost_for_tokens(1_500, 1_000, &exponential, 0).unwrap(), 1_505_000);
+
+        // Small prices still grow every step
+        let tiny = step_price(1, &exponential, 3);
+        assert_eq!(tiny, 4);
+
+        for payment in [999, 1_000, 2_000_000, 50_000_000] {
+            assert_max_affordable(payment, 1_000, &exponential, 400, 128_000);
+        }
+    }
+
+    #[test]
+    fn exponential_buy_across_every_step_stays_bounded() {
+        let max_supply = MAX_CURVE_STEPS * 1_000;
+        let exponential = curve(CurveType::Exponential, 500, 1_000);
+        exponential.validate(max_supply).unwrap();
+
+        // Buying the whole supply walks all steps once
+        let tokens = calculate_tokens_for_payment(u64::MAX, 1_000, &exponential, 0, max_supply).unwrap();
+        assert_eq!(tokens, max_supply);
+        let cost = calculate_cost_for_tokens(tokens, 1_000, &exponential, 0).unwrap();
+        assert!(cost > 1_000 * max_supply);
+    }
+
+    #[test]
+    fn stepped_cost_crosses_step_boundaries() {
+        let stepped = curve(CurveType::Stepped, 10, 1_000);
+
+        // 1000 tokens at 100, then 500 at 110
+        assert_eq!(calculate_cost_for_tokens(1_500, 100, &stepped, 0).unwrap(), 155_000);
+        // From mid-step: 200 at 110, then 300 at 120
+        assert_eq!(calculate_cost_for_tokens(500, 100, &stepped, 1_800).unwrap(), 58_000);
+
+        for payment in [99, 100, 155_000, 1_000_000] {
+            assert_max_affordable(payment, 100, &stepped, 1_800, 20_000);
+        }
+    }
+
+    #[test]
+    fn constant_product_buys_never_exceed_the_payment() {
+        let virtual_pool = curve(CurveType::ConstantProduct, 30_000, 1_000_000);
+        virtual_pool.validate(800_000).unwrap();
+
+        for sold in [0, 1, 500_000, 799_000] {
+            for payment in [1, 30, 1_000, 77_777, 10_000_000] {
+                assert_max_affordable(payment, 1, &virtual_pool, sold, 800_000);
+            }
+        }
+
+        // Buying n tokens costs R(T - n) - R(T)
+        let cost = calculate_cost_for_tokens(500_000, 1, &virtual_pool, 0).unwrap();
+        assert_eq!(cost, 30_000);
+    }
+
+    #[test]
+    fn purchases_are_capped_at_the_remaining_supply() {
+        let max_supply = 10_000;
+        for curve in [
+            curve(CurveType::Linear, 1, 1_000),
+            curve(CurveType::Exponential, 100, 100),
+            curve(CurveType::ConstantProduct, 1_000, 20_000),
+            curve(CurveType::Stepped, 1, 100),
+        ] {
+            curve.validate(max_supply).unwrap();
+
+            let tokens = calculate_tokens_for_payment(u64::MAX, 1, &curve, max_supply - 7, max_supply).unwrap();
+            assert_eq!(tokens, 7, "{:?}", curve.curve_type);
+
+            let tokens = calculate_tokens_for_payment(u64::MAX, 1, &curve, max_supply, max_supply).unwrap();
+            assert_eq!(tokens, 0, "{:?}", curve.curve_type);
+
+            assert_eq!(calculate_tokens_for_payment(0, 1, &curve, 0, max_supply).unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn raise_tracks_the_supply_sold_across_purchases() {
+        let max_supply = 50_000;
+        for curve in [
+            curve(CurveType::Linear, 3, 7),
+            curve(CurveType::Exponential, 100, 1_000),
+            curve(CurveType::ConstantProduct, 30_000, 60_000),
+            curve(CurveType::Stepped, 5, 1_000),
+        ] {
+            curve.validate(max_supply).unwrap();
+
+            let (mut raise, mut sold) = (0u64, 0u64);
+            for budget in [1, 13, 999, 4_321, 77_777, 1_000_000, u32::MAX as u64] {
+                let (tokens, cost) = price_purchase(raise, budget, 2, &curve, max_supply).unwrap();
+                assert!(cost <= budget, "{:?}: charged {} > {}", curve.curve_type, cost, budget);
+
+                raise += cost;
+                sold += tokens;
+
+                // The position derived from the raise is the supply handed out
+                let derived = calculate_tokens_for_payment(raise, 2, &curve, 0, max_supply).unwrap();
+                assert_eq!(derived, sold, "{:?}", curve.curve_type);
+                assert_eq!(calculate_cost_for_tokens(sold, 2, &curve, 0).unwrap(), raise);
+            }
+            assert!(sold <= max_supply);
+        }
+    }
+
+    #[test]
+    fn curve_validation_rejects_unbounded_curves() {
+        assert!(curve(CurveType::Linear, 1, 0).validate(100).is_err());
+        assert!(curve(CurveType::Stepped, 1, 1).validate(MAX_CURVE_STEPS + 1).is_err());
+        assert!(curve(CurveType::Exponential, 1, 0).validate(100).is_err());
+        assert!(curve(CurveType::ConstantProduct, 0, 1_000).validate(100).is_err());
+        assert!(curve(CurveType::ConstantProduct, 1, 100).validate(100).is_err());
+    }
 }
@@ -1,14 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Transfer};
-use crate::{AddLiquidityEncrypted, RemoveLiquidityPrivate, EncryptedPosition};
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use crate::{
+    AddLiquidityEncrypted, RemoveLiquidityPrivate, ClaimLpFees, AddConcentratedLiquidity,
+    RemoveConcentratedLiquidity, DarkPool, EncryptedPosition, PositionTree, PoolTicks, CircuitKind,
+    MINIMUM_LIQUIDITY, MIN_POSITION_LIQUIDITY,
+};
 use crate::errors::DarkFlowError;
+use crate::tick_math::{self, MAX_TICK, MIN_TICK};
 use crate::zk_verify;
 
 /// Add liquidity with encrypted amount
 pub fn add_liquidity_encrypted(
     ctx: Context<AddLiquidityEncrypted>,
     encrypted_amount: Vec<u8>,
-    commitment: [u8; 32],
+    note_commitment: [u8; 32],
     amount_a: u64,
     amount_b: u64,
 ) -> Result<()> {
@@ -16,47 +21,68 @@ pub fn add_liquidity_encrypted(
 
     // Validate pool is active
     require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
+
+    // Concentrated pools take range positions instead of full-range shares
+    require!(!pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Advance the TWAP accumulators before the reserves change
+    pool.accumulate_price(Clock::get()?.unix_timestamp);
 
     // Validate amounts
     require!(amount_a > 0 || amount_b > 0, DarkFlowError::InvalidAmount);
 
     // Validate encrypted data
     require!(
-        encrypted_amount.len() > 0 && encrypted_amount.len() <= 256,
+        !encrypted_amount.is_empty() && encrypted_amount.len() <= 256,
         DarkFlowError::InvalidEncryptedData
     );
 
-    // Validate commitment is not zero
-    require!(commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
+    // Validate note commitment is not zero
+    require!(note_commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
 
-    // Transfer token A to vault
-    if amount_a > 0 {
-        let transfer_a_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.lp_token_a.to_account_info(),
-                to: ctx.accounts.vault_a.to_account_info(),
-                authority: ctx.accounts.lp.to_account_info(),
-            },
-        );
-        token::transfer(transfer_a_ctx, amount_a)?;
+    // Mint shares against the reserves before this deposit
+    let (reserve_a, reserve_b) = pool.reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+    let mut shares = calculate_shares_for_deposit(
+        amount_a,
+        amount_b,
+        reserve_a,
+        reserve_b,
+        pool.total_shares,
+    )?;
+    let minted = shares;
+    if pool.total_shares == 0 {
+        require!(shares > MINIMUM_LIQUIDITY, DarkFlowError::InvalidAmount);
+        shares -= MINIMUM_LIQUIDITY;
     }
+    require!(shares > 0, DarkFlowError::InvalidAmount);
 
-    // Transfer token B to vault
-    if amount_b > 0 {
-        let transfer_b_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.lp_token_b.to_account_info(),
-                to: ctx.accounts.vault_b.to_account_info(),
-                authority: ctx.accounts.lp.to_account_info(),
-            },
-        );
-        token::transfer(transfer_b_ctx, amount_b)?;
-    }
+    // Transfer tokens to the vaults
+    transfer_to_pool_vaults(
+        &ctx.accounts.token_program,
+        &ctx.accounts.lp,
+        &ctx.accounts.lp_token_a,
+        &ctx.accounts.lp_token_b,
+        &ctx.accounts.vault_a,
+        &ctx.accounts.vault_b,
+        amount_a,
+        amount_b,
+    )?;
 
-    // Create nullifier for this position
-    let nullifier = create_nullifier(&commitment, &ctx.accounts.lp.key());
+    // Bind the minted shares into the position commitment, checkpointed at
+    // the current fee growth so the position only earns fees from here on
+    let commitment = EncryptedPosition::share_commitment(
+        shares,
+        pool.fee_growth_a,
+        pool.fee_growth_b,
+        &note_commitment,
+    );
+
+    // Append the commitment to the position tree; its root is the pool's
+    // state commitment
+    let position_tree = &mut ctx.accounts.position_tree;
+    let leaf_index = position_tree.append(commitment)?;
+    pool.update_state_commitment(position_tree.root())?;
 
     // Initialize position
     let position = &mut ctx.accounts.position;
@@ -67,67 +93,655 @@ pub fn add_liquidity_encrypted(
         pool.key(),
         encrypted_amount,
         commitment,
-        nullifier,
+        leaf_index,
         bump,
     )?;
 
     // Update pool state
     pool.increment_position_count();
+    pool.total_shares = pool
+        .total_shares
+        .checked_add(minted)
+        .ok_or(DarkFlowError::MathOverflow)?;
+
+    // Price the TWAP at the reserves this deposit leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
 
     msg!("Added encrypted liquidity");
     msg!("Position commitment: {:?}", &commitment[..8]);
+    msg!("Position leaf index: {}", leaf_index);
     msg!("Pool position count: {}", pool.position_count);
 
     Ok(())
 }
 
 /// Remove liquidity with ZK proof of ownership
+///
+/// The proof shows the position is a leaf of a recent position tree root
+/// without naming it. Its nullifier is derived from the LP's secret and
+/// recorded so the position can only be withdrawn once, without linking
+/// the withdrawal to the deposit.
+///
+/// The position's shares and fee checkpoint stay private witnesses: the
+/// statement carries only the shares withdrawn, the fees earned and the
+/// change commitment, which the proof shows opens to exactly the shares
+/// left. Fees earned since the checkpoint are paid along with the
+/// principal, so the change starts from the current fee growth.
+#[allow(clippy::too_many_arguments)]
 pub fn remove_liquidity_private(
     ctx: Context<RemoveLiquidityPrivate>,
-    position_commitment: [u8; 32],
+    merkle_root: [u8; 32],
+    nullifier: [u8; 32],
     zk_proof: Vec<u8>,
-    withdraw_percentage_bps: u16,
+    shares_to_burn: u64,
+    fees_a: u64,
+    fees_b: u64,
+    change_commitment: [u8; 32],
+    encrypted_change: Vec<u8>,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
-    let position = &ctx.accounts.position;
 
-    // Validate pool is active
+    // Validate pool is active, or exiting in emergency mode
+    require!(pool.accepts_withdrawals(), DarkFlowError::PoolNotActive);
+    require!(!pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Advance the TWAP accumulators before the reserves change
+    pool.accumulate_price(Clock::get()?.unix_timestamp);
+
+    // Validate the proof is made against a recent tree root
+    require!(
+        ctx.accounts.position_tree.is_known_root(&merkle_root),
+        DarkFlowError::UnknownMerkleRoot
+    );
+
+    // Validate the shares to burn
+    require!(shares_to_burn > 0, DarkFlowError::InvalidAmount);
+    require!(
+        shares_to_burn <= pool.total_shares,
+        DarkFlowError::InsufficientBalance
+    );
+
+    // A partial withdrawal must re-commit the remaining shares; a full exit
+    // leaves nothing behind
+    validate_withdrawal_change(
+        &change_commitment,
+        &encrypted_change,
+        ctx.accounts.change_position.is_some(),
+    )?;
+
+    // Validate ZK proof against the pool's pinned position circuit
+    let verifying_key = ctx
+        .accounts
+        .verifying_key
+        .pinned_key(pool.pinned_vk_version(CircuitKind::Position))?;
+    let statement = zk_verify::PositionStatement {
+        root: &merkle_root,
+        nullifier: &nullifier,
+        recipient_a: &ctx.accounts.recipient_token_a.key(),
+        recipient_b: &ctx.accounts.recipient_token_b.key(),
+        withdrawn_shares: shares_to_burn,
+        fees_a,
+        fees_b,
+        tick_lower: 0,
+        tick_upper: 0,
+        change_commitment: &change_commitment,
+        fee_growth_a: pool.fee_growth_a,
+        fee_growth_b: pool.fee_growth_b,
+    };
+    require!(
+        zk_verify::verify_position_proof(&zk_proof, &statement, verifying_key),
+        DarkFlowError::InvalidZkProof
+    );
+
+    // Record the nullifier (the account init fails if already spent)
+    let nullifier_account = &mut ctx.accounts.nullifier_account;
+    nullifier_account.nullifier = nullifier;
+    nullifier_account.used_at = Clock::get()?.unix_timestamp;
+
+    // Withdraw pro rata to the shares burned, excluding accrued fees
+    let (reserve_a, reserve_b) = pool.reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+    let withdraw_a = calculate_withdrawal_amount(shares_to_burn, reserve_a, pool.total_shares)?;
+    let withdraw_b = calculate_withdrawal_amount(shares_to_burn, reserve_b, pool.total_shares)?;
+
+    // Proven fees, paid out of the fees owed to LPs
+    pool.release_lp_fees(fees_a, fees_b)?;
+
+    // Transfer tokens to the recipient
+    transfer_from_pool_vaults(
+        &ctx.accounts.token_program,
+        pool,
+        &ctx.accounts.vault_a,
+        &ctx.accounts.vault_b,
+        &ctx.accounts.recipient_token_a,
+        &ctx.accounts.recipient_token_b,
+        withdraw_a.checked_add(fees_a).ok_or(DarkFlowError::MathOverflow)?,
+        withdraw_b.checked_add(fees_b).ok_or(DarkFlowError::MathOverflow)?,
+    )?;
+
+    // Keep the remaining shares open under the change commitment
+    if let Some(change_position) = ctx.accounts.change_position.as_mut() {
+        let leaf_index = append_position(
+            pool,
+            &mut ctx.accounts.position_tree,
+            change_position,
+            ctx.accounts.withdrawer.key(),
+            encrypted_change,
+            change_commitment,
+            ctx.bumps.change_position.ok_or(DarkFlowError::ChangePositionMismatch)?,
+        )?;
+
+        msg!("Remaining position leaf index: {}", leaf_index);
+    } else {
+        pool.decrement_position_count();
+    }
+
+    // Update pool state
+    pool.total_shares -= shares_to_burn;
+
+    // Price the TWAP at the reserves this withdrawal leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    msg!("Removed liquidity privately");
+    msg!("Fees paid: {} A, {} B", fees_a, fees_b);
+
+    Ok(())
+}
+
+/// Claim LP fees without withdrawing principal
+///
+/// Spends the position like a withdrawal of no shares and re-commits all
+/// of them with the current fee growth as the new checkpoint.
+#[allow(clippy::too_many_arguments)]
+pub fn claim_lp_fees(
+    ctx: Context<ClaimLpFees>,
+    merkle_root: [u8; 32],
+    nullifier: [u8; 32],
+    zk_proof: Vec<u8>,
+    fees_a: u64,
+    fees_b: u64,
+    change_commitment: [u8; 32],
+    encrypted_change: Vec<u8>,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    // Validate pool is active, or exiting in emergency mode
+    require!(pool.accepts_withdrawals(), DarkFlowError::PoolNotActive);
+    require!(!pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Validate the proof is made against a recent tree root
+    require!(
+        ctx.accounts.position_tree.is_known_root(&merkle_root),
+        DarkFlowError::UnknownMerkleRoot
+    );
+
+    require!(fees_a > 0 || fees_b > 0, DarkFlowError::InvalidAmount);
+    validate_change(&change_commitment, &encrypted_change)?;
+
+    // Validate ZK proof: all shares carry over to the change commitment
+    let verifying_key = ctx
+        .accounts
+        .verifying_key
+        .pinned_key(pool.pinned_vk_version(CircuitKind::Position))?;
+    let statement = zk_verify::PositionStatement {
+        root: &merkle_root,
+        nullifier: &nullifier,
+        recipient_a: &ctx.accounts.recipient_token_a.key(),
+        recipient_b: &ctx.accounts.recipient_token_b.key(),
+        withdrawn_shares: 0,
+        fees_a,
+        fees_b,
+        tick_lower: 0,
+        tick_upper: 0,
+        change_commitment: &change_commitment,
+        fee_growth_a: pool.fee_growth_a,
+        fee_growth_b: pool.fee_growth_b,
+    };
+    require!(
+        zk_verify::verify_position_proof(&zk_proof, &statement, verifying_key),
+        DarkFlowError::InvalidZkProof
+    );
+
+    // Record the nullifier (the account init fails if already spent)
+    let nullifier_account = &mut ctx.accounts.nullifier_account;
+    nullifier_account.nullifier = nullifier;
+    nullifier_account.used_at = Clock::get()?.unix_timestamp;
+
+    // Proven fees, paid out of the fees owed to LPs
+    pool.release_lp_fees(fees_a, fees_b)?;
+
+    transfer_from_pool_vaults(
+        &ctx.accounts.token_program,
+        pool,
+        &ctx.accounts.vault_a,
+        &ctx.accounts.vault_b,
+        &ctx.accounts.recipient_token_a,
+        &ctx.accounts.recipient_token_b,
+        fees_a,
+        fees_b,
+    )?;
+
+    let leaf_index = append_position(
+        pool,
+        &mut ctx.accounts.position_tree,
+        &mut ctx.accounts.change_position,
+        ctx.accounts.claimer.key(),
+        encrypted_change,
+        change_commitment,
+        ctx.bumps.change_position,
+    )?;
+
+    msg!("Claimed LP fees");
+    msg!("Fees paid: {} A, {} B", fees_a, fees_b);
+    msg!("Position leaf index: {}", leaf_index);
+
+    Ok(())
+}
+
+/// Add concentrated liquidity over a tick range
+///
+/// The range and liquidity are bound into the position commitment together
+/// with the fee growth inside the range, so the position account reveals
+/// neither. Only the per-tick aggregates in `pool_ticks` change publicly.
+#[allow(clippy::too_many_arguments)]
+pub fn add_concentrated_liquidity(
+    ctx: Context<AddConcentratedLiquidity>,
+    encrypted_amount: Vec<u8>,
+    note_commitment: [u8; 32],
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u64,
+    max_amount_a: u64,
+    max_amount_b: u64,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    // Validate pool is active and concentrated
     require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
+    require!(pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Advance the TWAP accumulators before the reserves change
+    pool.accumulate_price(Clock::get()?.unix_timestamp);
+
+    // Validate range and liquidity
+    validate_tick_range(tick_lower, tick_upper, pool.tick_spacing)?;
+    require!(liquidity >= MIN_POSITION_LIQUIDITY, DarkFlowError::PositionTooSmall);
+
+    // Validate encrypted data
+    require!(
+        !encrypted_amount.is_empty() && encrypted_amount.len() <= 256,
+        DarkFlowError::InvalidEncryptedData
+    );
+
+    // Validate note commitment is not zero
+    require!(note_commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
+
+    // Tokens needed for the liquidity at the current price, rounded up
+    let (amount_a, amount_b) = tick_math::position_amounts(
+        pool.sqrt_price,
+        pool.tick_current,
+        tick_lower,
+        tick_upper,
+        liquidity,
+        true,
+    )?;
+    require!(amount_a > 0 || amount_b > 0, DarkFlowError::InvalidAmount);
+    require!(
+        amount_a <= max_amount_a && amount_b <= max_amount_b,
+        DarkFlowError::SlippageExceeded
+    );
+
+    // Add the liquidity to both bounds, and to the active liquidity if the
+    // range contains the price
+    update_range_liquidity(pool, &mut ctx.accounts.pool_ticks, tick_lower, tick_upper, liquidity as i128)?;
+
+    transfer_to_pool_vaults(
+        &ctx.accounts.token_program,
+        &ctx.accounts.lp,
+        &ctx.accounts.lp_token_a,
+        &ctx.accounts.lp_token_b,
+        &ctx.accounts.vault_a,
+        &ctx.accounts.vault_b,
+        amount_a,
+        amount_b,
+    )?;
+
+    // Checkpoint the position at the fee growth inside its range
+    let (inside_a, inside_b) = ctx.accounts.pool_ticks.fee_growth_inside(
+        tick_lower,
+        tick_upper,
+        pool.tick_current,
+        pool.fee_growth_a,
+        pool.fee_growth_b,
+    );
+    let commitment = EncryptedPosition::share_commitment(
+        liquidity,
+        inside_a,
+        inside_b,
+        &EncryptedPosition::range_note(tick_lower, tick_upper, &note_commitment),
+    );
+
+    let leaf_index = append_position(
+        pool,
+        &mut ctx.accounts.position_tree,
+        &mut ctx.accounts.position,
+        ctx.accounts.lp.key(),
+        encrypted_amount,
+        commitment,
+        ctx.bumps.position,
+    )?;
+    pool.increment_position_count();
 
-    // Validate position is active
-    require!(position.is_active, DarkFlowError::PositionNotActive);
+    // Price the TWAP at the reserves this deposit leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
 
-    // Validate commitment matches
+    msg!("Added concentrated liquidity");
+    msg!("Position commitment: {:?}", &commitment[..8]);
+    msg!("Position leaf index: {}", leaf_index);
+    msg!("Pool position count: {}", pool.position_count);
+
+    Ok(())
+}
+
+/// Remove concentrated liquidity with ZK proof of ownership
+///
+/// Works like `remove_liquidity_private` with liquidity in place of
+/// shares. The proof binds the range, so the liquidity is taken off
+/// exactly the ticks it was added to, and fees are measured by the fee
+/// growth inside the range. The range itself is public, as the tick
+/// updates publish it anyway; the position's liquidity and checkpoint
+/// are not.
+#[allow(clippy::too_many_arguments)]
+pub fn remove_concentrated_liquidity(
+    ctx: Context<RemoveConcentratedLiquidity>,
+    merkle_root: [u8; 32],
+    nullifier: [u8; 32],
+    zk_proof: Vec<u8>,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity_to_burn: u64,
+    fees_a: u64,
+    fees_b: u64,
+    change_commitment: [u8; 32],
+    encrypted_change: Vec<u8>,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    // Validate pool is active (or exiting in emergency mode) and concentrated
+    require!(pool.accepts_withdrawals(), DarkFlowError::PoolNotActive);
+    require!(pool.is_concentrated(), DarkFlowError::PoolModeMismatch);
+
+    // Advance the TWAP accumulators before the reserves change
+    pool.accumulate_price(Clock::get()?.unix_timestamp);
+
+    // Validate the proof is made against a recent tree root
     require!(
-        position.commitment == position_commitment,
-        DarkFlowError::InvalidCommitment
+        ctx.accounts.position_tree.is_known_root(&merkle_root),
+        DarkFlowError::UnknownMerkleRoot
     );
 
-    // Validate ZK proof (Noir circuit verification)
+    validate_tick_range(tick_lower, tick_upper, pool.tick_spacing)?;
+    require!(liquidity_to_burn > 0, DarkFlowError::InvalidAmount);
+
+    // The proof keeps any remaining liquidity at MIN_POSITION_LIQUIDITY or
+    // more, so partial withdrawals cannot leave dust positions behind
+    validate_withdrawal_change(
+        &change_commitment,
+        &encrypted_change,
+        ctx.accounts.change_position.is_some(),
+    )?;
+
+    // Fee growth inside the range, before the ticks change
+    let (inside_a, inside_b) = ctx.accounts.pool_ticks.fee_growth_inside(
+        tick_lower,
+        tick_upper,
+        pool.tick_current,
+        pool.fee_growth_a,
+        pool.fee_growth_b,
+    );
+
+    // Validate ZK proof against the pool's pinned position circuit
+    let verifying_key = ctx
+        .accounts
+        .verifying_key
+        .pinned_key(pool.pinned_vk_version(CircuitKind::Position))?;
+    let statement = zk_verify::PositionStatement {
+        root: &merkle_root,
+        nullifier: &nullifier,
+        recipient_a: &ctx.accounts.recipient_token_a.key(),
+        recipient_b: &ctx.accounts.recipient_token_b.key(),
+        withdrawn_shares: liquidity_to_burn,
+        fees_a,
+        fees_b,
+        tick_lower,
+        tick_upper,
+        change_commitment: &change_commitment,
+        fee_growth_a: inside_a,
+        fee_growth_b: inside_b,
+    };
     require!(
-        zk_verify::verify_position_proof(&zk_proof, &position_commitment),
+        zk_verify::verify_position_proof(&zk_proof, &statement, verifying_key),
         DarkFlowError::InvalidZkProof
     );
 
-    // Validate withdrawal percentage
+    // Record the nullifier (the account init fails if already spent)
+    let nullifier_account = &mut ctx.accounts.nullifier_account;
+    nullifier_account.nullifier = nullifier;
+    nullifier_account.used_at = Clock::get()?.unix_timestamp;
+
+    // Tokens backing the burned liquidity, rounded down
+    let (withdraw_a, withdraw_b) = tick_math::position_amounts(
+        pool.sqrt_price,
+        pool.tick_current,
+        tick_lower,
+        tick_upper,
+        liquidity_to_burn,
+        false,
+    )?;
+
+    // Proven fees earned inside the range, paid out of the fees owed to LPs
+    pool.release_lp_fees(fees_a, fees_b)?;
+
+    update_range_liquidity(
+        pool,
+        &mut ctx.accounts.pool_ticks,
+        tick_lower,
+        tick_upper,
+        -(liquidity_to_burn as i128),
+    )?;
+
+    transfer_from_pool_vaults(
+        &ctx.accounts.token_program,
+        pool,
+        &ctx.accounts.vault_a,
+        &ctx.accounts.vault_b,
+        &ctx.accounts.recipient_token_a,
+        &ctx.accounts.recipient_token_b,
+        withdraw_a.checked_add(fees_a).ok_or(DarkFlowError::MathOverflow)?,
+        withdraw_b.checked_add(fees_b).ok_or(DarkFlowError::MathOverflow)?,
+    )?;
+
+    // Keep the remaining liquidity open under the change commitment
+    if let Some(change_position) = ctx.accounts.change_position.as_mut() {
+        let leaf_index = append_position(
+            pool,
+            &mut ctx.accounts.position_tree,
+            change_position,
+            ctx.accounts.withdrawer.key(),
+            encrypted_change,
+            change_commitment,
+            ctx.bumps.change_position.ok_or(DarkFlowError::ChangePositionMismatch)?,
+        )?;
+
+        msg!("Remaining position leaf index: {}", leaf_index);
+    } else {
+        pool.decrement_position_count();
+    }
+
+    // Price the TWAP at the reserves this withdrawal leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    msg!("Removed concentrated liquidity privately");
+    msg!("Fees paid: {} A, {} B", fees_a, fees_b);
+
+    Ok(())
+}
+
+/// Validate position bounds: ordered, on the tick spacing and in range
+fn validate_tick_range(tick_lower: i32, tick_upper: i32, tick_spacing: u16) -> Result<()> {
+    let spacing = tick_spacing as i32;
     require!(
-        withdraw_percentage_bps > 0 && withdraw_percentage_bps <= 10000,
-        DarkFlowError::InvalidAmount
+        tick_lower < tick_upper
+            && tick_lower >= MIN_TICK
+            && tick_upper <= MAX_TICK
+            && tick_lower % spacing == 0
+            && tick_upper % spacing == 0,
+        DarkFlowError::InvalidTickRange
     );
+    Ok(())
+}
+
+/// Add a signed liquidity change to a range's bounds, and to the active
+/// liquidity if the range contains the current price
+fn update_range_liquidity(
+    pool: &mut DarkPool,
+    pool_ticks: &mut PoolTicks,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity_delta: i128,
+) -> Result<()> {
+    for (tick, is_upper) in [(tick_lower, false), (tick_upper, true)] {
+        pool_ticks.update(
+            tick,
+            pool.tick_spacing,
+            pool.tick_current,
+            liquidity_delta,
+            pool.fee_growth_a,
+            pool.fee_growth_b,
+            is_upper,
+        )?;
+    }
+
+    if tick_lower <= pool.tick_current && pool.tick_current < tick_upper {
+        pool.liquidity = tick_math::add_liquidity_delta(pool.liquidity, liquidity_delta)?;
+    }
+    Ok(())
+}
 
-    // Calculate withdrawal amounts
-    // In production, this would be computed via MPC or derived from the proof
-    let vault_a_balance = ctx.accounts.vault_a.amount;
-    let vault_b_balance = ctx.accounts.vault_b.amount;
+/// Validate the commitment and encrypted data of a change position
+fn validate_change(change_commitment: &[u8; 32], encrypted_change: &[u8]) -> Result<()> {
+    require!(*change_commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
+    require!(
+        !encrypted_change.is_empty() && encrypted_change.len() <= 256,
+        DarkFlowError::InvalidEncryptedData
+    );
+    Ok(())
+}
 
-    // Simplified: withdraw proportional to position count
-    // In production, use encrypted share from position
-    let share = 10000u64 / pool.position_count.max(1);
-    let withdraw_a = (vault_a_balance * share * withdraw_percentage_bps as u64) / (10000 * 10000);
-    let withdraw_b = (vault_b_balance * share * withdraw_percentage_bps as u64) / (10000 * 10000);
+/// Validate the change of a withdrawal: a partial withdrawal must
+/// re-commit what remains, and a full exit leaves nothing behind
+///
+/// The proof shows the change commitment is zero exactly when nothing
+/// remains.
+fn validate_withdrawal_change(
+    change_commitment: &[u8; 32],
+    encrypted_change: &[u8],
+    has_change_position: bool,
+) -> Result<()> {
+    if *change_commitment != [0u8; 32] {
+        validate_change(change_commitment, encrypted_change)?;
+        require!(has_change_position, DarkFlowError::ChangePositionMismatch);
+    } else {
+        require!(*change_commitment == [0u8; 32], DarkFlowError::InvalidCommitment);
+        require!(!has_change_position, DarkFlowError::ChangePositionMismatch);
+    }
+    Ok(())
+}
 
-    // Transfer tokens back to owner
-    let _pool_key = pool.key();
+/// Append a position commitment to the position tree and record its position
+fn append_position(
+    pool: &mut Account<DarkPool>,
+    position_tree: &mut PositionTree,
+    position: &mut Account<EncryptedPosition>,
+    owner: Pubkey,
+    encrypted_data: Vec<u8>,
+    commitment: [u8; 32],
+    bump: u8,
+) -> Result<u32> {
+    let leaf_index = position_tree.append(commitment)?;
+    pool.update_state_commitment(position_tree.root())?;
+
+    **position = EncryptedPosition::new(
+        owner,
+        pool.key(),
+        encrypted_data,
+        commitment,
+        leaf_index,
+        bump,
+    )?;
+
+    Ok(leaf_index)
+}
+
+/// Transfer tokens from an LP into the pool vaults
+#[allow(clippy::too_many_arguments)]
+fn transfer_to_pool_vaults<'info>(
+    token_program: &Program<'info, Token>,
+    authority: &Signer<'info>,
+    from_a: &Account<'info, TokenAccount>,
+    from_b: &Account<'info, TokenAccount>,
+    vault_a: &Account<'info, TokenAccount>,
+    vault_b: &Account<'info, TokenAccount>,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<()> {
+    if amount_a > 0 {
+        let transfer_a_ctx = CpiContext::new(
+            token_program.to_account_info(),
+            Transfer {
+                from: from_a.to_account_info(),
+                to: vault_a.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        );
+        token::transfer(transfer_a_ctx, amount_a)?;
+    }
+
+    if amount_b > 0 {
+        let transfer_b_ctx = CpiContext::new(
+            token_program.to_account_info(),
+            Transfer {
+                from: from_b.to_account_info(),
+                to: vault_b.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+        );
+        token::transfer(transfer_b_ctx, amount_b)?;
+    }
+
+    Ok(())
+}
+
+/// Transfer tokens out of the pool vaults, signed by the pool
+#[allow(clippy::too_many_arguments)]
+fn transfer_from_pool_vaults<'info>(
+    token_program: &Program<'info, Token>,
+    pool: &Account<'info, DarkPool>,
+    vault_a: &Account<'info, TokenAccount>,
+    vault_b: &Account<'info, TokenAccount>,
+    to_a: &Account<'info, TokenAccount>,
+    to_b: &Account<'info, TokenAccount>,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<()> {
     let seeds = &[
         b"dark_pool",
         pool.token_a_mint.as_ref(),
@@ -136,49 +750,83 @@ pub fn remove_liquidity_private(
     ];
     let signer = &[&seeds[..]];
 
-    if withdraw_a > 0 {
+    if amount_a > 0 {
         let transfer_a_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
+            token_program.to_account_info(),
             Transfer {
-                from: ctx.accounts.vault_a.to_account_info(),
-                to: ctx.accounts.owner_token_a.to_account_info(),
+                from: vault_a.to_account_info(),
+                to: to_a.to_account_info(),
                 authority: pool.to_account_info(),
             },
             signer,
         );
-        token::transfer(transfer_a_ctx, withdraw_a)?;
+        token::transfer(transfer_a_ctx, amount_a)?;
     }
 
-    if withdraw_b > 0 {
+    if amount_b > 0 {
         let transfer_b_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
+            token_program.to_account_info(),
             Transfer {
-                from: ctx.accounts.vault_b.to_account_info(),
-                to: ctx.accounts.owner_token_b.to_account_info(),
+                from: vault_b.to_account_info(),
+                to: to_b.to_account_info(),
                 authority: pool.to_account_info(),
             },
             signer,
         );
-        token::transfer(transfer_b_ctx, withdraw_b)?;
+        token::transfer(transfer_b_ctx, amount_b)?;
     }
 
-    // Update pool state
-    if withdraw_percentage_bps == 10000 {
-        pool.decrement_position_count();
+    Ok(())
+}
+
+/// LP shares minted for a deposit
+///
+/// The first deposit mints `sqrt(amount_a * amount_b)`. Later deposits mint
+/// the smaller of the two reserve ratios, so unbalanced deposits donate the
+/// excess to existing LPs rather than diluting them.
+fn calculate_shares_for_deposit(
+    amount_a: u64,
+    amount_b: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_shares: u64,
+) -> Result<u64> {
+    if total_shares == 0 {
+        let product = (amount_a as u128)
+            .checked_mul(amount_b as u128)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        return Ok(isqrt(product) as u64);
     }
 
-    msg!("Removed liquidity privately");
-    msg!("Withdrew {}% of position", withdraw_percentage_bps / 100);
+    require!(reserve_a > 0 && reserve_b > 0, DarkFlowError::InsufficientBalance);
 
-    Ok(())
+    let shares_a = (amount_a as u128 * total_shares as u128) / reserve_a as u128;
+    let shares_b = (amount_b as u128 * total_shares as u128) / reserve_b as u128;
+
+    u64::try_from(shares_a.min(shares_b)).map_err(|_| error!(DarkFlowError::MathOverflow))
 }
 
-/// Create a nullifier from commitment and owner using blake3 with domain separation
-fn create_nullifier(commitment: &[u8; 32], owner: &Pubkey) -> [u8; 32] {
-    let mut input = [0u8; 82];
-    input[..32].copy_from_slice(commitment);
-    input[32..64].copy_from_slice(owner.as_ref());
-    input[64..82].copy_from_slice(b"darkflow_nullifier");
-    *blake3::hash(&input).as_bytes()
+/// Token amount owed for burning `shares` out of `total_shares`
+fn calculate_withdrawal_amount(shares: u64, reserve: u64, total_shares: u64) -> Result<u64> {
+    let amount = (reserve as u128)
+        .checked_mul(shares as u128)
+        .ok_or(DarkFlowError::MathOverflow)?
+        .checked_div(total_shares as u128)
+        .ok_or(DarkFlowError::MathOverflow)?;
+
+    Ok(amount as u64)
 }
 
+/// Integer square root (floor)
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
@@ -1,11 +1,17 @@
 pub mod pool;
 pub mod liquidity;
 pub mod swap;
+pub mod batch;
 pub mod launch;
 pub mod query;
+pub mod verifying_key;
+pub mod solver;
 
 pub use pool::*;
 pub use liquidity::*;
 pub use swap::*;
+pub use batch::*;
 pub use launch::*;
 pub use query::*;
+pub use verifying_key::*;
+pub use solver::*;
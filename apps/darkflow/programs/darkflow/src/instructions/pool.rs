@@ -1,6 +1,13 @@
 use anchor_lang::prelude::*;
-use crate::{InitializePool, InitializePoolVaults, UpdatePoolConfig};
+use anchor_spl::token::{self, Transfer};
+use crate::{
+    AcceptPoolAuthority, CollectProtocolFees, InitializePool, InitializePoolVaults, InitializePositionTree,
+    InitializePriceOracle, InitializeConcentratedLiquidity, SwapCurve, UpdatePoolConfig,
+    VOLUME_BUCKETS,
+};
 use crate::errors::DarkFlowError;
+use crate::stable_math::{MAX_AMPLIFICATION, MAX_AMPLIFICATION_CHANGE, MIN_RAMP_DURATION};
+use crate::tick_math::{self, MAX_TICK_SPACING};
 
 /// Initialize a new dark liquidity pool
 pub fn initialize_pool(
@@ -9,10 +16,23 @@ pub fn initialize_pool(
     token_b_mint: Pubkey,
     pool_encryption_pubkey: [u8; 32],
     fee_rate_bps: u16,
+    curve: SwapCurve,
+    amplification: u64,
 ) -> Result<()> {
     // Validate fee rate
     require!(fee_rate_bps <= 10000, DarkFlowError::InvalidAmount);
 
+    // Only StableSwap pools are amplified
+    match curve {
+        SwapCurve::ConstantProduct => {
+            require!(amplification == 0, DarkFlowError::InvalidAmplification)
+        }
+        SwapCurve::StableSwap => require!(
+            amplification > 0 && amplification <= MAX_AMPLIFICATION,
+            DarkFlowError::InvalidAmplification
+        ),
+    }
+
     // Validate encryption key is not zero
     require!(
         pool_encryption_pubkey != [0u8; 32],
@@ -23,23 +43,55 @@ pub fn initialize_pool(
     let bump = ctx.bumps.pool;
 
     pool.authority = ctx.accounts.authority.key();
+    pool.pending_authority = Pubkey::default();
     pool.token_a_mint = token_a_mint;
     pool.token_b_mint = token_b_mint;
     pool.encryption_pubkey = pool_encryption_pubkey;
     pool.fee_rate_bps = fee_rate_bps;
+    pool.protocol_fee_bps = 0;
+    pool.treasury = Pubkey::default();
     pool.position_count = 0;
+    pool.total_shares = 0;
+    pool.fee_growth_a = 0;
+    pool.fee_growth_b = 0;
+    pool.lp_fees_owed_a = 0;
+    pool.lp_fees_owed_b = 0;
+    pool.protocol_fees_owed_a = 0;
+    pool.protocol_fees_owed_b = 0;
+    pool.tick_spacing = 0;
+    pool.sqrt_price = 0;
+    pool.tick_current = 0;
+    pool.liquidity = 0;
+    pool.curve = curve;
+    pool.initial_amplification = amplification;
+    pool.future_amplification = amplification;
+    pool.initial_amplification_time = Clock::get()?.unix_timestamp;
+    pool.future_amplification_time = Clock::get()?.unix_timestamp;
     pool.order_count = 0;
     pool.total_volume_a = 0;
     pool.total_volume_b = 0;
+    pool.volume_buckets_a = [0; VOLUME_BUCKETS];
+    pool.volume_buckets_b = [0; VOLUME_BUCKETS];
+    pool.volume_hour = 0;
+    pool.price_a_cumulative = 0;
+    pool.price_b_cumulative = 0;
+    pool.price_timestamp_last = Clock::get()?.unix_timestamp;
+    pool.reserve_a_last = 0;
+    pool.reserve_b_last = 0;
     pool.state_commitment = [0u8; 32];
     pool.last_update = Clock::get()?.unix_timestamp;
     pool.is_active = true;
+    pool.emergency_mode = false;
+    pool.swap_vk_version = 0;
+    pool.execution_vk_version = 0;
+    pool.position_vk_version = 0;
     pool.bump = bump;
 
     msg!("DarkFlow pool initialized");
     msg!("Token A: {}", token_a_mint);
     msg!("Token B: {}", token_b_mint);
     msg!("Fee rate: {} bps", fee_rate_bps);
+    msg!("Curve: {:?}", curve);
 
     Ok(())
 }
@@ -54,6 +106,80 @@ pub fn initialize_pool_vaults(
     Ok(())
 }
 
+/// Create the pool's position tree
+///
+/// The empty tree root becomes the pool's state commitment. Deposits
+/// require the tree to exist.
+pub fn initialize_position_tree(ctx: Context<InitializePositionTree>) -> Result<()> {
+    let tree = &mut ctx.accounts.position_tree;
+    tree.initialize(ctx.accounts.pool.key(), ctx.bumps.position_tree);
+
+    let root = tree.root();
+    ctx.accounts.pool.update_state_commitment(root)?;
+
+    msg!("Position tree initialized");
+
+    Ok(())
+}
+
+/// Create the pool's TWAP price oracle
+///
+/// Trades require the oracle to exist and record observations into it.
+pub fn initialize_price_oracle(ctx: Context<InitializePriceOracle>) -> Result<()> {
+    let oracle = &mut ctx.accounts.price_oracle;
+    oracle.pool = ctx.accounts.pool.key();
+    oracle.observations = Vec::new();
+    oracle.observation_index = 0;
+    oracle.bump = ctx.bumps.price_oracle;
+
+    msg!("Price oracle initialized");
+
+    Ok(())
+}
+
+/// Switch an empty pool to concentrated liquidity
+///
+/// Creates the pool's tick state and starts the price at
+/// `initial_sqrt_price`. From then on liquidity is added over tick ranges
+/// and swaps walk the initialized ticks instead of the constant product.
+pub fn initialize_concentrated_liquidity(
+    ctx: Context<InitializeConcentratedLiquidity>,
+    tick_spacing: u16,
+    initial_sqrt_price: u128,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    // Ticks price on the constant product, and full-range shares cannot
+    // be converted to ranges
+    require!(pool.curve == SwapCurve::ConstantProduct, DarkFlowError::PoolModeMismatch);
+    require!(
+        !pool.is_concentrated() && pool.total_shares == 0 && pool.position_count == 0,
+        DarkFlowError::PoolModeMismatch
+    );
+    require!(
+        tick_spacing > 0 && tick_spacing <= MAX_TICK_SPACING,
+        DarkFlowError::InvalidTickRange
+    );
+
+    pool.tick_spacing = tick_spacing;
+    pool.tick_current = tick_math::tick_at_sqrt_price(initial_sqrt_price)?;
+    pool.sqrt_price = initial_sqrt_price;
+    pool.liquidity = 0;
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    let ticks = &mut ctx.accounts.pool_ticks;
+    ticks.pool = pool.key();
+    ticks.ticks = Vec::new();
+    ticks.bitmap = Vec::new();
+    ticks.bump = ctx.bumps.pool_ticks;
+
+    msg!("Concentrated liquidity enabled");
+    msg!("Tick spacing: {}", tick_spacing);
+    msg!("Current tick: {}", pool.tick_current);
+
+    Ok(())
+}
+
 /// Update pool configuration
 pub fn update_pool_config(
     ctx: Context<UpdatePoolConfig>,
@@ -81,3 +207,218 @@ pub fn update_pool_config(
 
     Ok(())
 }
+
+/// Pause or resume the pool
+///
+/// A paused pool accepts no trades, deposits or withdrawals, unless
+/// emergency mode keeps withdrawals open.
+pub fn set_pool_active(ctx: Context<UpdatePoolConfig>, is_active: bool) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.is_active = is_active;
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Pool active: {}", is_active);
+
+    Ok(())
+}
+
+/// Enter or leave emergency mode
+///
+/// In emergency mode swaps, dark orders, batches and deposits stop, while
+/// LPs can still withdraw and claim fees with their position proofs, even
+/// if the pool is also paused. Pending orders can still be cancelled or
+/// expired to release their escrow.
+pub fn set_emergency_mode(ctx: Context<UpdatePoolConfig>, emergency_mode: bool) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.emergency_mode = emergency_mode;
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Emergency mode: {}", emergency_mode);
+
+    Ok(())
+}
+
+/// Propose a new pool authority
+///
+/// The transfer only happens once the proposed authority accepts it, so a
+/// mistyped key cannot lock the pool. Proposing the default key cancels a
+/// pending proposal.
+pub fn propose_pool_authority(ctx: Context<UpdatePoolConfig>, new_authority: Pubkey) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    pool.pending_authority = new_authority;
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Proposed pool authority: {}", new_authority);
+
+    Ok(())
+}
+
+/// Accept a proposed pool authority transfer
+pub fn accept_pool_authority(ctx: Context<AcceptPoolAuthority>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let new_authority = ctx.accounts.pending_authority.key();
+    require!(
+        pool.pending_authority != Pubkey::default() && pool.pending_authority == new_authority,
+        DarkFlowError::NotPendingAuthority
+    );
+
+    let previous_authority = pool.authority;
+    pool.authority = new_authority;
+    pool.pending_authority = Pubkey::default();
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Pool authority transferred from {} to {}", previous_authority, new_authority);
+
+    Ok(())
+}
+
+/// Set the protocol's share of swap fees and the treasury receiving it
+///
+/// A default (all-zero) treasury disables the protocol fee. Fees already
+/// accrued are paid to whichever treasury is set when they are collected.
+pub fn set_protocol_fee(
+    ctx: Context<UpdatePoolConfig>,
+    protocol_fee_bps: u16,
+    treasury: Pubkey,
+) -> Result<()> {
+    require!(protocol_fee_bps <= 10000, DarkFlowError::InvalidAmount);
+
+    let pool = &mut ctx.accounts.pool;
+    pool.protocol_fee_bps = protocol_fee_bps;
+    pool.treasury = treasury;
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Protocol fee set to {} bps of swap fees", protocol_fee_bps);
+    msg!("Treasury: {}", treasury);
+
+    Ok(())
+}
+
+/// Start moving a StableSwap pool's amplification towards a new value
+///
+/// The amplification moves linearly from its current value to
+/// `future_amplification` at `future_time`, so the curve never jumps
+/// under LPs. Ramps are bounded in size and frequency.
+pub fn ramp_amplification(
+    ctx: Context<UpdatePoolConfig>,
+    future_amplification: u64,
+    future_time: i64,
+) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(pool.curve == SwapCurve::StableSwap, DarkFlowError::PoolModeMismatch);
+    require!(
+        now >= pool.initial_amplification_time.saturating_add(MIN_RAMP_DURATION)
+            && future_time >= now.saturating_add(MIN_RAMP_DURATION),
+        DarkFlowError::InvalidAmplification
+    );
+    require!(
+        future_amplification > 0 && future_amplification <= MAX_AMPLIFICATION,
+        DarkFlowError::InvalidAmplification
+    );
+
+    let current = pool.amplification(now);
+    require!(
+        future_amplification.saturating_mul(MAX_AMPLIFICATION_CHANGE) >= current
+            && future_amplification <= current.saturating_mul(MAX_AMPLIFICATION_CHANGE),
+        DarkFlowError::InvalidAmplification
+    );
+
+    pool.initial_amplification = current;
+    pool.future_amplification = future_amplification;
+    pool.initial_amplification_time = now;
+    pool.future_amplification_time = future_time;
+    pool.last_update = now;
+
+    msg!("Amplification ramping from {} to {}", current, future_amplification);
+    msg!("Ramp ends at: {}", future_time);
+
+    Ok(())
+}
+
+/// Freeze a StableSwap pool's amplification at its current value
+pub fn stop_amplification_ramp(ctx: Context<UpdatePoolConfig>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(pool.curve == SwapCurve::StableSwap, DarkFlowError::PoolModeMismatch);
+
+    let current = pool.amplification(now);
+    pool.initial_amplification = current;
+    pool.future_amplification = current;
+    pool.initial_amplification_time = now;
+    pool.future_amplification_time = now;
+    pool.last_update = now;
+
+    msg!("Amplification fixed at {}", current);
+
+    Ok(())
+}
+
+/// Pay accrued protocol fees to the treasury's token accounts
+pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+    require!(pool.treasury != Pubkey::default(), DarkFlowError::TreasuryNotSet);
+
+    let treasury_a = &ctx.accounts.treasury_token_a;
+    require!(treasury_a.owner == pool.treasury, DarkFlowError::Unauthorized);
+    require!(
+        treasury_a.mint == pool.token_a_mint,
+        DarkFlowError::InvalidTokenMint
+    );
+
+    let treasury_b = &ctx.accounts.treasury_token_b;
+    require!(treasury_b.owner == pool.treasury, DarkFlowError::Unauthorized);
+    require!(
+        treasury_b.mint == pool.token_b_mint,
+        DarkFlowError::InvalidTokenMint
+    );
+
+    let amount_a = pool.protocol_fees_owed_a;
+    let amount_b = pool.protocol_fees_owed_b;
+    require!(amount_a > 0 || amount_b > 0, DarkFlowError::InvalidAmount);
+
+    let seeds = &[
+        b"dark_pool",
+        pool.token_a_mint.as_ref(),
+        pool.token_b_mint.as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    if amount_a > 0 {
+        let transfer_a_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.vault_a.to_account_info(),
+                to: ctx.accounts.treasury_token_a.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(transfer_a_ctx, amount_a)?;
+    }
+
+    if amount_b > 0 {
+        let transfer_b_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.vault_b.to_account_info(),
+                to: ctx.accounts.treasury_token_b.to_account_info(),
+                authority: pool.to_account_info(),
+            },
+            signer,
+        );
+        token::transfer(transfer_b_ctx, amount_b)?;
+    }
+
+    pool.protocol_fees_owed_a = 0;
+    pool.protocol_fees_owed_b = 0;
+
+    msg!("Protocol fees collected");
+    msg!("Token A: {}", amount_a);
+    msg!("Token B: {}", amount_b);
+
+    Ok(())
+}
@@ -1,18 +1,61 @@
 use anchor_lang::prelude::*;
-use crate::{QueryPoolAggregates, PoolAggregates};
+use crate::{
+    QueryPoolAggregates, ConsultTwap, PoolAggregates, PriceOracle, SwapCurve, TwapQuote,
+    CUMULATIVE_PRICE_SCALE, PRICE_SCALE,
+};
+use crate::errors::DarkFlowError;
+use crate::instructions::swap::calculate_spot_price;
+use crate::tick_math;
 
 /// Query pool aggregates (public data only)
 pub fn query_pool_aggregates(ctx: Context<QueryPoolAggregates>) -> Result<PoolAggregates> {
     let pool = &ctx.accounts.pool;
+    let vault_a = ctx.accounts.vault_a.amount;
+    let vault_b = ctx.accounts.vault_b.amount;
+    let now = Clock::get()?.unix_timestamp;
+
+    // Price off the reserves backing LP shares, excluding accrued fees, or
+    // off the current tick price in concentrated mode
+    let (reserve_a, reserve_b) = pool.reserves(vault_a, vault_b);
+    let spot_price = if pool.is_concentrated() {
+        tick_math::mul_div(pool.sqrt_price, pool.sqrt_price, CUMULATIVE_PRICE_SCALE)
+            .and_then(|price| tick_math::mul_div(price, PRICE_SCALE, CUMULATIVE_PRICE_SCALE))
+            .unwrap_or(0)
+    } else if reserve_a == 0 {
+        0
+    } else if pool.curve == SwapCurve::StableSwap {
+        calculate_spot_price(reserve_a, reserve_b, pool.curve, pool.amplification(now)).unwrap_or(0)
+    } else {
+        reserve_b as u128 * PRICE_SCALE / reserve_a as u128
+    };
+
+    // Value everything in the vaults in each token at the spot price
+    let (tvl_token_a, tvl_token_b) = match (vault_b as u128 * PRICE_SCALE).checked_div(spot_price) {
+        Some(vault_b_in_a) => (
+            saturating_u64(vault_a as u128 + vault_b_in_a),
+            saturating_u64(vault_b as u128 + (vault_a as u128).saturating_mul(spot_price) / PRICE_SCALE),
+        ),
+        None => (vault_a, vault_b),
+    };
+
+    // Every trade has a token A leg, so token A volume over TVL is turnover
+    let (volume_24h_a, volume_24h_b) = pool.volume_24h(now);
+    let utilization_bps = if tvl_token_a == 0 {
+        0
+    } else {
+        (volume_24h_a as u128 * 10000 / tvl_token_a as u128).min(10000) as u16
+    };
 
     // Return public aggregates
     // Individual positions remain private
     let aggregates = PoolAggregates {
-        tvl_token_a: 0, // Would be fetched from vault
-        tvl_token_b: 0, // Would be fetched from vault
+        tvl_token_a,
+        tvl_token_b,
         lp_count: pool.position_count,
-        volume_24h: pool.total_volume_a + pool.total_volume_b, // Simplified
-        utilization_bps: 0, // Would be calculated
+        volume_24h_a,
+        volume_24h_b,
+        spot_price,
+        utilization_bps,
         avg_position_commitment: pool.state_commitment,
     };
 
@@ -21,3 +64,32 @@ pub fn query_pool_aggregates(ctx: Context<QueryPoolAggregates>) -> Result<PoolAg
 
     Ok(aggregates)
 }
+
+/// Time-weighted average price over at least `window` seconds
+///
+/// Averages from the newest observation at least `window` seconds old to
+/// the pool's cumulative prices extrapolated to now at the reserves the
+/// latest trade or liquidity change left, never the live vault balances.
+pub fn consult_twap(ctx: Context<ConsultTwap>, window: u32) -> Result<TwapQuote> {
+    require!(window > 0, DarkFlowError::InvalidAmount);
+
+    let pool = &ctx.accounts.pool;
+    let now = Clock::get()?.unix_timestamp;
+    let current = pool.price_observation(now);
+
+    let start = ctx
+        .accounts
+        .price_oracle
+        .observation_before(now - window as i64)
+        .ok_or(DarkFlowError::TwapWindowUnavailable)?;
+    let quote = PriceOracle::twap(start, &current)?;
+
+    msg!("TWAP over {}s: {} B per A (1e12)", quote.window, quote.price_a);
+
+    Ok(quote)
+}
+
+/// Clamp a u128 amount to u64
+fn saturating_u64(value: u128) -> u64 {
+    u64::try_from(value).unwrap_or(u64::MAX)
+}
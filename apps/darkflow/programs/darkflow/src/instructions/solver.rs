@@ -0,0 +1,172 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use crate::{
+    InitializeSolverRegistry, RegisterSolver, SetSolverActive, RemoveSolver, SlashSolver,
+    RequestSolverUnbond, WithdrawSolverBond, SolverEntry, MAX_SOLVERS, SOLVER_UNBOND_COOLDOWN,
+};
+use crate::errors::DarkFlowError;
+
+/// Create the solver registry for a pool
+pub fn initialize_solver_registry(
+    ctx: Context<InitializeSolverRegistry>,
+    min_bond: u64,
+) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+
+    registry.pool = ctx.accounts.pool.key();
+    registry.min_bond = min_bond;
+    registry.solvers = Vec::new();
+    registry.bump = ctx.bumps.registry;
+
+    msg!("Solver registry created");
+    msg!("Minimum bond: {}", min_bond);
+
+    Ok(())
+}
+
+/// Register as a solver by posting a bond
+///
+/// The solver cannot execute orders until the pool authority activates it.
+pub fn register_solver(ctx: Context<RegisterSolver>, bond: u64) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+    let solver = ctx.accounts.solver.key();
+
+    require!(bond >= registry.min_bond, DarkFlowError::InsufficientBond);
+    require!(registry.find(&solver).is_none(), DarkFlowError::SolverAlreadyRegistered);
+    require!(registry.solvers.len() < MAX_SOLVERS, DarkFlowError::SolverRegistryFull);
+
+    // Bond is held as lamports on the registry account
+    let transfer_ctx = CpiContext::new(
+        ctx.accounts.system_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.solver.to_account_info(),
+            to: registry.to_account_info(),
+        },
+    );
+    system_program::transfer(transfer_ctx, bond)?;
+
+    registry.solvers.push(SolverEntry {
+        solver,
+        bond,
+        is_active: false,
+        orders_filled: 0,
+        registered_at: Clock::get()?.unix_timestamp,
+        unbond_requested_at: 0,
+    });
+
+    msg!("Solver registered: {}", solver);
+    msg!("Bond: {}", bond);
+
+    Ok(())
+}
+
+/// Activate or deactivate a registered solver (authority only)
+pub fn set_solver_active(
+    ctx: Context<SetSolverActive>,
+    solver: Pubkey,
+    is_active: bool,
+) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+
+    let entry = registry.find_mut(&solver)?;
+    require!(!(is_active && entry.is_unbonding()), DarkFlowError::SolverUnbonding);
+    entry.is_active = is_active;
+
+    msg!("Solver {} active: {}", solver, is_active);
+
+    Ok(())
+}
+
+/// Remove a solver and return its bond (authority only)
+pub fn remove_solver(ctx: Context<RemoveSolver>) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+    let solver = ctx.accounts.solver.key();
+
+    let entry = registry.remove(&solver)?;
+    release_bond(&registry.to_account_info(), &ctx.accounts.solver.to_account_info(), entry.bond)?;
+
+    msg!("Solver removed: {}", solver);
+    msg!("Bond returned: {}", entry.bond);
+
+    Ok(())
+}
+
+/// Remove a misbehaving solver and send its bond to the treasury (authority only)
+///
+/// Unbonding solvers can still be slashed until they withdraw.
+pub fn slash_solver(ctx: Context<SlashSolver>, solver: Pubkey) -> Result<()> {
+    require!(
+        ctx.accounts.pool.treasury != Pubkey::default(),
+        DarkFlowError::TreasuryNotSet
+    );
+
+    let registry = &mut ctx.accounts.registry;
+
+    let entry = registry.remove(&solver)?;
+    release_bond(&registry.to_account_info(), &ctx.accounts.treasury.to_account_info(), entry.bond)?;
+
+    msg!("Solver slashed: {}", solver);
+    msg!("Bond to treasury: {}", entry.bond);
+
+    Ok(())
+}
+
+/// Stop executing orders and start the unbonding cooldown (solver only)
+pub fn request_solver_unbond(ctx: Context<RequestSolverUnbond>) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+    let solver = ctx.accounts.solver.key();
+
+    let entry = registry.find_mut(&solver)?;
+    require!(!entry.is_unbonding(), DarkFlowError::SolverUnbonding);
+
+    entry.is_active = false;
+    entry.unbond_requested_at = Clock::get()?.unix_timestamp;
+
+    msg!("Solver unbonding: {}", solver);
+    msg!("Bond withdrawable after: {}", entry.unbond_requested_at + SOLVER_UNBOND_COOLDOWN);
+
+    Ok(())
+}
+
+/// Leave the registry and withdraw the bond once the cooldown has passed
+/// (solver only)
+pub fn withdraw_solver_bond(ctx: Context<WithdrawSolverBond>) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+    let solver = ctx.accounts.solver.key();
+
+    let entry = registry.find(&solver).ok_or(DarkFlowError::SolverNotRegistered)?;
+    require!(entry.is_unbonding(), DarkFlowError::UnbondCooldownActive);
+    let unlocks_at = entry
+        .unbond_requested_at
+        .checked_add(SOLVER_UNBOND_COOLDOWN)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    require!(
+        Clock::get()?.unix_timestamp >= unlocks_at,
+        DarkFlowError::UnbondCooldownActive
+    );
+
+    let entry = registry.remove(&solver)?;
+    release_bond(&registry.to_account_info(), &ctx.accounts.solver.to_account_info(), entry.bond)?;
+
+    msg!("Solver unbonded: {}", solver);
+    msg!("Bond returned: {}", entry.bond);
+
+    Ok(())
+}
+
+// Helper functions
+
+/// Move a bond out of the registry account
+///
+/// The registry is program-owned, so the bond can be debited directly.
+fn release_bond(registry: &AccountInfo, to: &AccountInfo, bond: u64) -> Result<()> {
+    **registry.try_borrow_mut_lamports()? = registry
+        .lamports()
+        .checked_sub(bond)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    **to.try_borrow_mut_lamports()? = to
+        .lamports()
+        .checked_add(bond)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    Ok(())
+}
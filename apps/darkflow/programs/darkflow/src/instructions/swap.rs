@@ -1,30 +1,69 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Transfer};
-use crate::{DarkSwap, SubmitDarkOrder, ExecuteDarkOrder, CancelDarkOrder, DarkOrder, OrderStatus};
+use anchor_spl::token::{self, CloseAccount, Transfer};
+use crate::{
+    DarkSwap, SubmitDarkOrder, ExecuteDarkOrder, CancelDarkOrder, ExpireDarkOrder, DarkOrder,
+    OrderStatus, OrderReveal, SwapDirection, CircuitKind, DarkPool, PoolTicks, SwapCurve,
+    EXPIRY_CRANK_REWARD_LAMPORTS, PRICE_SCALE,
+};
 use crate::errors::DarkFlowError;
+use crate::stable_math;
+use crate::tick_math::{self, MAX_TICK, MIN_TICK};
 use crate::zk_verify;
 
 /// Execute a dark swap with ZK proof
+///
+/// The proof binds `input_amount` and `min_output_amount` as public inputs,
+/// so the trade settled here is exactly the one the user proved.
 pub fn dark_swap(
     ctx: Context<DarkSwap>,
     encrypted_order: Vec<u8>,
     zk_proof: Vec<u8>,
     nullifier: [u8; 32],
+    input_amount: u64,
+    min_output_amount: u64,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     // Validate pool is active
     require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
 
     // Validate encrypted order
     require!(
-        encrypted_order.len() > 0 && encrypted_order.len() <= 256,
+        !encrypted_order.is_empty() && encrypted_order.len() <= 256,
         DarkFlowError::InvalidEncryptedData
     );
 
-    // Validate ZK proof (Noir circuit verification)
+    // Validate amounts
+    require!(input_amount > 0, DarkFlowError::InvalidAmount);
+    require!(min_output_amount > 0, DarkFlowError::InvalidAmount);
+
+    // Determine direction from the user's input token
+    let input_mint = ctx.accounts.user_input_token.mint;
+    let output_mint = ctx.accounts.user_output_token.mint;
+    let a_to_b = if input_mint == pool.token_a_mint && output_mint == pool.token_b_mint {
+        true
+    } else if input_mint == pool.token_b_mint && output_mint == pool.token_a_mint {
+        false
+    } else {
+        return err!(DarkFlowError::InvalidTokenMint);
+    };
+
+    // Validate ZK proof against the pool's pinned swap circuit
+    let verifying_key = ctx
+        .accounts
+        .verifying_key
+        .pinned_key(pool.pinned_vk_version(CircuitKind::Swap))?;
     require!(
-        zk_verify::verify_swap_proof(&zk_proof, &encrypted_order, &nullifier),
+        zk_verify::verify_swap_proof(
+            &zk_proof,
+            &encrypted_order,
+            &nullifier,
+            &pool.key(),
+            input_amount,
+            min_output_amount,
+            verifying_key,
+        ),
         DarkFlowError::InvalidZkProof
     );
 
@@ -35,15 +74,79 @@ pub fn dark_swap(
     nullifier_account.nullifier = nullifier;
     nullifier_account.used_at = Clock::get()?.unix_timestamp;
 
-    // Execute the swap
-    // In production, amounts would be extracted from decrypted order or computed via MPC
-    // For now, use a simplified swap model
+    let (vault_input, vault_output) = if a_to_b {
+        (&ctx.accounts.vault_a, &ctx.accounts.vault_b)
+    } else {
+        (&ctx.accounts.vault_b, &ctx.accounts.vault_a)
+    };
 
-    msg!("Dark swap executed");
-    msg!("Nullifier: {:?}", &nullifier[..8]);
+    // Advance the TWAP accumulators to now at the reserves the last change left
+    let observation = pool.accumulate_price(Clock::get()?.unix_timestamp);
+    ctx.accounts.price_oracle.record(observation);
+
+    let (reserve_a, reserve_b) = pool.reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    // Settle against the pool and set the fee aside for LPs
+    let output_amount = swap_against_pool(
+        pool,
+        ctx.accounts.pool_ticks.as_mut(),
+        reserve_a,
+        reserve_b,
+        input_amount,
+        a_to_b,
+    )?;
+
+    require!(output_amount > 0, DarkFlowError::InsufficientBalance);
+    require!(
+        output_amount >= min_output_amount,
+        DarkFlowError::SlippageExceeded
+    );
+
+    // Transfer input from user to the pool
+    let transfer_input_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.user_input_token.to_account_info(),
+            to: vault_input.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    token::transfer(transfer_input_ctx, input_amount)?;
+
+    // Transfer output from the pool to user
+    let pool_seeds = &[
+        b"dark_pool",
+        pool.token_a_mint.as_ref(),
+        pool.token_b_mint.as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&pool_seeds[..]];
+
+    let transfer_output_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: vault_output.to_account_info(),
+            to: ctx.accounts.user_output_token.to_account_info(),
+            authority: pool.to_account_info(),
+        },
+        signer,
+    );
+    token::transfer(transfer_output_ctx, output_amount)?;
 
     // Update volume
-    pool.add_volume(0, 0); // Actual amounts hidden
+    if a_to_b {
+        pool.add_volume(input_amount, output_amount)?;
+    } else {
+        pool.add_volume(output_amount, input_amount)?;
+    }
+
+    // Price the TWAP at the reserves this trade leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    msg!("Dark swap executed");
+    msg!("Nullifier: {:?}", &nullifier[..8]);
 
     Ok(())
 }
@@ -55,11 +158,13 @@ pub fn submit_dark_order(
     commitment: [u8; 32],
     input_amount: u64,
     deadline: i64,
+    direction: SwapDirection,
 ) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     // Validate pool is active
     require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
 
     // Validate deadline is in the future
     let now = Clock::get()?.unix_timestamp;
@@ -68,12 +173,22 @@ pub fn submit_dark_order(
     // Validate input amount
     require!(input_amount > 0, DarkFlowError::InvalidAmount);
 
+    // Validate commitment
+    require!(commitment != [0u8; 32], DarkFlowError::InvalidCommitment);
+
     // Validate encrypted params
     require!(
-        encrypted_params.len() > 0 && encrypted_params.len() <= 256,
+        !encrypted_params.is_empty() && encrypted_params.len() <= 256,
         DarkFlowError::InvalidEncryptedData
     );
 
+    // The escrowed mint must be the input side of the chosen direction
+    let (input_mint, output_mint) = direction.mints(pool.token_a_mint, pool.token_b_mint);
+    require!(
+        ctx.accounts.input_mint.key() == input_mint,
+        DarkFlowError::InvalidTokenMint
+    );
+
     // Transfer input tokens to escrow
     let transfer_ctx = CpiContext::new(
         ctx.accounts.token_program.to_account_info(),
@@ -92,8 +207,9 @@ pub fn submit_dark_order(
     **order = DarkOrder::new(
         ctx.accounts.maker.key(),
         pool.key(),
-        ctx.accounts.maker_input_token.mint,
-        Pubkey::default(), // Output mint will be determined by pool
+        direction,
+        input_mint,
+        output_mint,
         encrypted_params,
         commitment,
         input_amount,
@@ -106,6 +222,7 @@ pub fn submit_dark_order(
 
     msg!("Dark order submitted");
     msg!("Order ID: {}", order.key());
+    msg!("Direction: {:?}", direction);
     msg!("Deadline: {}", deadline);
 
     Ok(())
@@ -114,7 +231,7 @@ pub fn submit_dark_order(
 /// Execute a dark order (solver only)
 pub fn execute_dark_order(
     ctx: Context<ExecuteDarkOrder>,
-    decrypted_min_output: u64,
+    reveal: OrderReveal,
     execution_proof: Vec<u8>,
     encrypted_output: Vec<u8>,
 ) -> Result<()> {
@@ -123,42 +240,69 @@ pub fn execute_dark_order(
 
     // Validate pool is active
     require!(pool.is_active, DarkFlowError::PoolNotActive);
+    require!(!pool.emergency_mode, DarkFlowError::EmergencyMode);
 
     // Validate order can be executed
     require!(order.can_execute()?, DarkFlowError::OrderNotPending);
 
-    // Validate execution proof (Noir circuit verification)
+    // Only active registered solvers may execute
+    let solver = ctx.accounts.solver.key();
     require!(
-        zk_verify::verify_execution_proof(&execution_proof, &order.commitment),
-        DarkFlowError::InvalidZkProof
+        ctx.accounts.solver_registry.is_active_solver(&solver),
+        DarkFlowError::UnauthorizedSolver
     );
 
+    // The revealed parameters must open the maker's commitment
+    order.verify_reveal(&reveal)?;
+
     // Execute swap through pool
     let input_amount = order.input_amount;
-
-    // Calculate output based on AMM formula
-    // In production, this would use the actual pool reserves and encrypted state
-    let output_amount = calculate_swap_output(
+    let a_to_b = order.direction == SwapDirection::AToB;
+    let (vault_input, vault_output) = if a_to_b {
+        (&ctx.accounts.vault_a, &ctx.accounts.vault_b)
+    } else {
+        (&ctx.accounts.vault_b, &ctx.accounts.vault_a)
+    };
+
+    // Advance the TWAP accumulators to now at the reserves the last change left
+    let observation = pool.accumulate_price(Clock::get()?.unix_timestamp);
+    ctx.accounts.price_oracle.record(observation);
+
+    let (reserve_a, reserve_b) = pool.reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
+
+    // Calculate output against the pool and set the fee aside for LPs
+    let output_amount = swap_against_pool(
+        pool,
+        ctx.accounts.pool_ticks.as_mut(),
+        reserve_a,
+        reserve_b,
         input_amount,
-        ctx.accounts.vault_input.amount,
-        ctx.accounts.vault_output.amount,
-        pool.fee_rate_bps,
+        a_to_b,
     )?;
+    require!(output_amount > 0, DarkFlowError::InsufficientBalance);
 
     // Validate slippage
     require!(
-        output_amount >= decrypted_min_output,
+        output_amount >= reveal.min_output,
         DarkFlowError::SlippageExceeded
     );
 
+    // Validate execution proof against the computed output and the pool's pinned circuit
+    let verifying_key = ctx
+        .accounts
+        .verifying_key
+        .pinned_key(pool.pinned_vk_version(CircuitKind::Execution))?;
+    require!(
+        zk_verify::verify_execution_proof(&execution_proof, &order.commitment, output_amount, verifying_key),
+        DarkFlowError::InvalidZkProof
+    );
+
     // Validate encrypted output length (NaCl box: 24-byte nonce + 16-byte tag + 8-byte amount = 48 min)
     require!(
         encrypted_output.len() >= 48 && encrypted_output.len() <= 64,
         DarkFlowError::InvalidEncryptedData
     );
 
-    // Transfer from escrow to vault
-    // Transfer output to maker
     let pool_seeds = &[
         b"dark_pool",
         pool.token_a_mint.as_ref(),
@@ -167,10 +311,23 @@ pub fn execute_dark_order(
     ];
     let signer = &[&pool_seeds[..]];
 
+    // Deliver the escrowed input to the pool
+    let transfer_input_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.escrow_token.to_account_info(),
+            to: vault_input.to_account_info(),
+            authority: pool.to_account_info(),
+        },
+        signer,
+    );
+    token::transfer(transfer_input_ctx, input_amount)?;
+
+    // Transfer output to maker
     let transfer_output_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
         Transfer {
-            from: ctx.accounts.vault_output.to_account_info(),
+            from: vault_output.to_account_info(),
             to: ctx.accounts.maker_output_token.to_account_info(),
             authority: pool.to_account_info(),
         },
@@ -178,15 +335,42 @@ pub fn execute_dark_order(
     );
     token::transfer(transfer_output_ctx, output_amount)?;
 
+    // Return anything donated to the escrow and close it, returning its rent to the maker
+    let remainder = ctx
+        .accounts
+        .escrow_token
+        .amount
+        .checked_sub(input_amount)
+        .ok_or(DarkFlowError::InsufficientBalance)?;
+    close_order_escrow(
+        ctx.accounts.token_program.to_account_info(),
+        pool.to_account_info(),
+        signer,
+        ctx.accounts.escrow_token.to_account_info(),
+        remainder,
+        ctx.accounts.maker_input_token.to_account_info(),
+        ctx.accounts.maker.to_account_info(),
+    )?;
+
     // Mark order as filled with solver-provided encrypted output
-    order.fill(ctx.accounts.solver.key(), encrypted_output)?;
+    order.fill(solver, encrypted_output)?;
+    ctx.accounts.solver_registry.record_fill(&solver)?;
 
     // Update volume
-    pool.add_volume(input_amount, output_amount);
+    if a_to_b {
+        pool.add_volume(input_amount, output_amount)?;
+    } else {
+        pool.add_volume(output_amount, input_amount)?;
+    }
+
+    // Price the TWAP at the reserves this trade leaves
+    ctx.accounts.vault_a.reload()?;
+    ctx.accounts.vault_b.reload()?;
+    pool.record_reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount);
 
     msg!("Dark order executed");
     msg!("Order: {}", order.key());
-    msg!("Solver: {}", ctx.accounts.solver.key());
+    msg!("Solver: {}", solver);
 
     Ok(())
 }
@@ -201,60 +385,278 @@ pub fn cancel_dark_order(ctx: Context<CancelDarkOrder>) -> Result<()> {
         DarkFlowError::OrderNotPending
     );
 
-    // Return escrowed tokens to maker
-    let input_amount = order.input_amount;
+    // Return the whole escrow balance to maker, signed by the pool that owns the escrow
+    let returned = ctx.accounts.escrow_token.amount;
+    let pool = &ctx.accounts.pool;
+    let pool_seeds = &[
+        b"dark_pool",
+        pool.token_a_mint.as_ref(),
+        pool.token_b_mint.as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&pool_seeds[..]];
 
-    let transfer_ctx = CpiContext::new(
+    close_order_escrow(
         ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: ctx.accounts.escrow_token.to_account_info(),
-            to: ctx.accounts.maker_input_token.to_account_info(),
-            authority: ctx.accounts.maker.to_account_info(),
-        },
-    );
-    token::transfer(transfer_ctx, input_amount)?;
+        pool.to_account_info(),
+        signer,
+        ctx.accounts.escrow_token.to_account_info(),
+        returned,
+        ctx.accounts.maker_input_token.to_account_info(),
+        ctx.accounts.maker.to_account_info(),
+    )?;
 
     // Mark order as cancelled
     order.cancel();
 
     msg!("Dark order cancelled");
-    msg!("Returned {} tokens to maker", input_amount);
+    msg!("Returned {} tokens to maker", returned);
+
+    Ok(())
+}
+
+/// Expire a pending dark order past its deadline (anyone can call)
+pub fn expire_dark_order(ctx: Context<ExpireDarkOrder>) -> Result<()> {
+    let order = &mut ctx.accounts.order;
+
+    // Validate order is pending and past its deadline
+    require!(
+        order.status == OrderStatus::Pending,
+        DarkFlowError::OrderNotPending
+    );
+    require!(order.is_expired()?, DarkFlowError::OrderNotExpired);
+
+    // Return the whole escrow balance to maker, signed by the pool that owns the escrow
+    let returned = ctx.accounts.escrow_token.amount;
+    let pool = &ctx.accounts.pool;
+    let pool_seeds = &[
+        b"dark_pool",
+        pool.token_a_mint.as_ref(),
+        pool.token_b_mint.as_ref(),
+        &[pool.bump],
+    ];
+    let signer = &[&pool_seeds[..]];
+
+    close_order_escrow(
+        ctx.accounts.token_program.to_account_info(),
+        pool.to_account_info(),
+        signer,
+        ctx.accounts.escrow_token.to_account_info(),
+        returned,
+        ctx.accounts.maker_input_token.to_account_info(),
+        ctx.accounts.maker.to_account_info(),
+    )?;
+
+    order.expire();
+
+    // Pay the crank reward out of the order rent; the `close` constraint
+    // sends the remainder to the maker
+    let order_info = order.to_account_info();
+    let cranker_info = ctx.accounts.cranker.to_account_info();
+    let reward = EXPIRY_CRANK_REWARD_LAMPORTS.min(order_info.lamports());
+    **order_info.try_borrow_mut_lamports()? -= reward;
+    **cranker_info.try_borrow_mut_lamports()? = cranker_info
+        .lamports()
+        .checked_add(reward)
+        .ok_or(DarkFlowError::MathOverflow)?;
+
+    msg!("Dark order expired");
+    msg!("Returned {} tokens to maker", returned);
+    msg!("Crank reward: {}", reward);
 
     Ok(())
 }
 
 // Helper functions
 
-fn calculate_swap_output(
+/// Send what is left in an order escrow to the maker and close it
+///
+/// Anyone can transfer into an escrow, so callers pass the balance beyond
+/// what they already moved out rather than assuming it is zero; a donation
+/// goes to the maker instead of blocking the close.
+pub(crate) fn close_order_escrow<'info>(
+    token_program: AccountInfo<'info>,
+    pool: AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+    escrow_token: AccountInfo<'info>,
+    remainder: u64,
+    maker_input_token: AccountInfo<'info>,
+    maker: AccountInfo<'info>,
+) -> Result<()> {
+    if remainder > 0 {
+        let transfer_ctx = CpiContext::new_with_signer(
+            token_program.clone(),
+            Transfer {
+                from: escrow_token.clone(),
+                to: maker_input_token,
+                authority: pool.clone(),
+            },
+            signer,
+        );
+        token::transfer(transfer_ctx, remainder)?;
+    }
+
+    let close_ctx = CpiContext::new_with_signer(
+        token_program,
+        CloseAccount {
+            account: escrow_token,
+            destination: maker,
+            authority: pool,
+        },
+        signer,
+    );
+    token::close_account(close_ctx)
+}
+
+/// Price a swap against the pool and accrue its fee
+///
+/// Full-range pools trade on the pool's curve over the reserves.
+/// Concentrated pools trade against the liquidity in range and credit the
+/// fee step by step.
+pub(crate) fn swap_against_pool(
+    pool: &mut DarkPool,
+    pool_ticks: Option<&mut Box<Account<PoolTicks>>>,
+    reserve_a: u64,
+    reserve_b: u64,
+    input_amount: u64,
+    a_to_b: bool,
+) -> Result<u64> {
+    if pool.is_concentrated() {
+        let pool_ticks = pool_ticks.ok_or(DarkFlowError::PoolModeMismatch)?;
+        return swap_concentrated(pool, pool_ticks, input_amount, a_to_b);
+    }
+
+    let (reserve_in, reserve_out) = if a_to_b {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+    let output_amount = calculate_swap_output(
+        input_amount,
+        reserve_in,
+        reserve_out,
+        pool.fee_rate_bps,
+        pool.curve,
+        pool.amplification(Clock::get()?.unix_timestamp),
+    )?;
+    pool.accrue_swap_fee(input_amount, a_to_b)?;
+
+    Ok(output_amount)
+}
+
+/// Swap against the concentrated liquidity in range, crossing initialized
+/// ticks as the price moves
+///
+/// Each step's fee is credited to the liquidity active during it. Fails if
+/// the whole input cannot be filled before the price reaches the end of
+/// the tick range.
+pub(crate) fn swap_concentrated(
+    pool: &mut DarkPool,
+    pool_ticks: &mut PoolTicks,
+    input_amount: u64,
+    a_to_b: bool,
+) -> Result<u64> {
+    let bound_tick = if a_to_b { MIN_TICK } else { MAX_TICK };
+    let sqrt_price_limit = tick_math::sqrt_price_at_tick(bound_tick)?;
+    let mut remaining = input_amount;
+    let mut output_amount = 0u64;
+
+    while remaining > 0 && pool.sqrt_price != sqrt_price_limit {
+        let next_tick = pool_ticks.next_initialized_tick(pool.tick_current, pool.tick_spacing, a_to_b);
+        let target_tick = next_tick.unwrap_or(bound_tick).clamp(MIN_TICK, MAX_TICK);
+        let sqrt_price_target = tick_math::sqrt_price_at_tick(target_tick)?;
+
+        let step = tick_math::compute_swap_step(
+            pool.sqrt_price,
+            sqrt_price_target,
+            pool.liquidity,
+            remaining,
+            pool.fee_rate_bps,
+        )?;
+        remaining -= step.amount_in + step.fee_amount;
+        output_amount = output_amount
+            .checked_add(step.amount_out)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        pool.credit_fee(step.fee_amount, a_to_b, pool.liquidity)?;
+
+        if step.sqrt_price_next == sqrt_price_target && next_tick.is_some() {
+            // Crossing a tick adds or removes the positions bounded by it
+            let liquidity_net = pool_ticks.cross(target_tick, pool.fee_growth_a, pool.fee_growth_b)?;
+            let delta = if a_to_b { -liquidity_net } else { liquidity_net };
+            pool.liquidity = tick_math::add_liquidity_delta(pool.liquidity, delta)?;
+            pool.tick_current = if a_to_b { target_tick - 1 } else { target_tick };
+        } else {
+            pool.tick_current = tick_math::tick_at_sqrt_price(step.sqrt_price_next)?;
+        }
+        pool.sqrt_price = step.sqrt_price_next;
+    }
+
+    require!(remaining == 0, DarkFlowError::InsufficientLiquidity);
+    Ok(output_amount)
+}
+
+/// Output of swapping `input` through the pool's curve
+///
+/// Both curves take the fee off the input before pricing it, and callers
+/// set the same fee aside for LPs with `DarkPool::accrue_swap_fee`.
+pub(crate) fn calculate_swap_output(
     input: u64,
     reserve_in: u64,
     reserve_out: u64,
     fee_bps: u16,
+    curve: SwapCurve,
+    amplification: u64,
 ) -> Result<u64> {
-    // Constant product formula: x * y = k
-    // output = (reserve_out * input * (10000 - fee)) / (reserve_in * 10000 + input * (10000 - fee))
-
     let fee_factor = 10000u128 - fee_bps as u128;
-    let numerator = (reserve_out as u128)
-        .checked_mul(input as u128)
-        .ok_or(DarkFlowError::MathOverflow)?
-        .checked_mul(fee_factor)
-        .ok_or(DarkFlowError::MathOverflow)?;
 
-    let denominator = (reserve_in as u128)
-        .checked_mul(10000)
-        .ok_or(DarkFlowError::MathOverflow)?
-        .checked_add(
-            (input as u128)
-                .checked_mul(fee_factor)
+    match curve {
+        SwapCurve::ConstantProduct => {
+            // Constant product formula: x * y = k
+            // output = (reserve_out * input * (10000 - fee)) / (reserve_in * 10000 + input * (10000 - fee))
+            let numerator = (reserve_out as u128)
+                .checked_mul(input as u128)
                 .ok_or(DarkFlowError::MathOverflow)?
-        )
-        .ok_or(DarkFlowError::MathOverflow)?;
-
-    let output = numerator
-        .checked_div(denominator)
-        .ok_or(DarkFlowError::MathOverflow)?;
+                .checked_mul(fee_factor)
+                .ok_or(DarkFlowError::MathOverflow)?;
 
-    Ok(output as u64)
+            let denominator = (reserve_in as u128)
+                .checked_mul(10000)
+                .ok_or(DarkFlowError::MathOverflow)?
+                .checked_add(
+                    (input as u128)
+                        .checked_mul(fee_factor)
+                        .ok_or(DarkFlowError::MathOverflow)?
+                )
+                .ok_or(DarkFlowError::MathOverflow)?;
+
+            let output = numerator
+                .checked_div(denominator)
+                .ok_or(DarkFlowError::MathOverflow)?;
+
+            Ok(output as u64)
+        }
+        SwapCurve::StableSwap => {
+            let input_less_fee = (input as u128 * fee_factor / 10000) as u64;
+            stable_math::swap_output(input_less_fee, reserve_in, reserve_out, amplification)
+        }
+    }
 }
 
+/// Marginal price of the input token in the output token on the pool's
+/// curve, scaled by `PRICE_SCALE`
+pub(crate) fn calculate_spot_price(
+    reserve_in: u64,
+    reserve_out: u64,
+    curve: SwapCurve,
+    amplification: u64,
+) -> Result<u128> {
+    require!(reserve_in > 0 && reserve_out > 0, DarkFlowError::InsufficientBalance);
+
+    match curve {
+        SwapCurve::ConstantProduct => Ok((reserve_out as u128)
+            .checked_mul(PRICE_SCALE)
+            .ok_or(DarkFlowError::MathOverflow)?
+            / reserve_in as u128),
+        SwapCurve::StableSwap => stable_math::spot_price(reserve_in, reserve_out, amplification, PRICE_SCALE),
+    }
+}
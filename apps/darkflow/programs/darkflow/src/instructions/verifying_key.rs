@@ -0,0 +1,120 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use crate::{
+    InitializeVerifyingKeyRegistry, WriteVerifyingKey, ActivateVerifyingKey, PinVerifyingKey,
+    CircuitKind, MAX_VERIFYING_KEY_LEN,
+};
+use crate::errors::DarkFlowError;
+use crate::groth16::Groth16VerifyingKey;
+
+/// Create the verifying key registry for one of the pool's circuits
+pub fn initialize_verifying_key_registry(
+    ctx: Context<InitializeVerifyingKeyRegistry>,
+    circuit: CircuitKind,
+) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+
+    registry.pool = ctx.accounts.pool.key();
+    registry.circuit = circuit;
+    registry.version = 0;
+    registry.key_hash = [0u8; 32];
+    registry.key_data = Vec::new();
+    registry.previous_version = 0;
+    registry.previous_key_hash = [0u8; 32];
+    registry.previous_key_data = Vec::new();
+    registry.staged_key_data = Vec::new();
+    registry.updated_at = Clock::get()?.unix_timestamp;
+    registry.bump = ctx.bumps.registry;
+
+    msg!("Verifying key registry created for {:?} circuit", circuit);
+
+    Ok(())
+}
+
+/// Append a chunk of a new verifying key to the staging buffer
+///
+/// Writing at offset 0 discards any previously staged data.
+pub fn write_verifying_key(
+    ctx: Context<WriteVerifyingKey>,
+    offset: u32,
+    data: Vec<u8>,
+) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+
+    require!(!data.is_empty(), DarkFlowError::InvalidVerifyingKey);
+
+    if offset == 0 {
+        registry.staged_key_data.clear();
+    }
+
+    // Chunks must be written sequentially
+    require!(
+        offset as usize == registry.staged_key_data.len(),
+        DarkFlowError::InvalidVerifyingKey
+    );
+    require!(
+        registry.staged_key_data.len() + data.len() <= MAX_VERIFYING_KEY_LEN,
+        DarkFlowError::InvalidVerifyingKey
+    );
+
+    registry.staged_key_data.extend_from_slice(&data);
+
+    msg!("Staged {} verifying key bytes", registry.staged_key_data.len());
+
+    Ok(())
+}
+
+/// Promote the staged key to the active key and bump the version
+///
+/// `expected_key_hash` must equal SHA-256 of the staged key, so the
+/// authority commits to exactly the key exported from the circuit build.
+/// The replaced key is retained as the previous version.
+pub fn activate_verifying_key(
+    ctx: Context<ActivateVerifyingKey>,
+    expected_key_hash: [u8; 32],
+) -> Result<()> {
+    let registry = &mut ctx.accounts.registry;
+
+    require!(
+        Groth16VerifyingKey::from_bytes(&registry.staged_key_data).is_some(),
+        DarkFlowError::InvalidVerifyingKey
+    );
+
+    let key_hash = hash(&registry.staged_key_data).to_bytes();
+    require!(key_hash == expected_key_hash, DarkFlowError::InvalidVerifyingKey);
+
+    registry.previous_version = registry.version;
+    registry.previous_key_hash = registry.key_hash;
+    registry.previous_key_data = std::mem::take(&mut registry.key_data);
+
+    registry.key_data = std::mem::take(&mut registry.staged_key_data);
+    registry.key_hash = key_hash;
+    registry.version = registry.version.checked_add(1).ok_or(DarkFlowError::MathOverflow)?;
+    registry.updated_at = Clock::get()?.unix_timestamp;
+
+    msg!("Activated {:?} verifying key v{}", registry.circuit, registry.version);
+    msg!("Key hash: {:?}", &key_hash[..8]);
+
+    Ok(())
+}
+
+/// Pin a retained key version as the one the pool accepts
+///
+/// Either the active or the previous version can be pinned, so a pool can
+/// move to a new key once provers have switched, or back if it misbehaves.
+pub fn pin_verifying_key(ctx: Context<PinVerifyingKey>, version: u32) -> Result<()> {
+    let registry = &ctx.accounts.registry;
+    let pool = &mut ctx.accounts.pool;
+
+    require!(
+        registry.has_version(version),
+        DarkFlowError::VerifyingKeyVersionMismatch
+    );
+
+    pool.pin_vk_version(registry.circuit, version);
+    pool.last_update = Clock::get()?.unix_timestamp;
+
+    msg!("Pool pinned {:?} verifying key v{}", registry.circuit, version);
+
+    Ok(())
+}
@@ -1,25 +1,28 @@
+//! DarkFlow - Confidential AMM with Dark Liquidity
+//!
+//! Key Features:
+//! - Encrypted LP positions (nobody knows your deposit size)
+//! - Dark swaps with ZK proofs (MEV impossible)
+//! - Confidential token launches on public bonding curves
+//! - Arcium integration for encrypted shared state
+//!
+//! Privacy Model:
+//! - Individual positions are encrypted
+//! - Pool aggregates (TVL, volume) are public
+//! - Swaps are verified via ZK proofs without revealing amounts
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount};
 
 declare_id!("8UvUSCfsXUjRW6NwcLVEJ4Y5jg8nWbxsZGNrzK1xs38U");
 
-/// DarkFlow - Confidential AMM with Dark Liquidity
-///
-/// Key Features:
-/// - Encrypted LP positions (nobody knows your deposit size)
-/// - Dark swaps with ZK proofs (MEV impossible)
-/// - Confidential token launches with private bonding curves
-/// - Arcium integration for encrypted shared state
-///
-/// Privacy Model:
-/// - Individual positions are encrypted
-/// - Pool aggregates (TVL, volume) are public
-/// - Swaps are verified via ZK proofs without revealing amounts
-
 pub mod state;
 pub mod instructions;
 pub mod errors;
+pub mod groth16;
 pub mod zk_verify;
+pub mod tick_math;
+pub mod stable_math;
 
 pub use state::*;
 pub use errors::*;
@@ -37,14 +40,28 @@ pub mod darkflow {
     /// The pool stores encrypted LP positions. Only aggregate statistics
     /// (total liquidity, number of LPs) are public.
     /// Call initialize_pool_vaults after this to create the token vaults.
+    ///
+    /// # Arguments
+    /// * `curve` - Swap invariant: constant product, or StableSwap for like-priced tokens
+    /// * `amplification` - StableSwap amplification coefficient (0 for constant product)
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
         token_a_mint: Pubkey,
         token_b_mint: Pubkey,
         pool_encryption_pubkey: [u8; 32],
         fee_rate_bps: u16,
+        curve: SwapCurve,
+        amplification: u64,
     ) -> Result<()> {
-        instructions::initialize_pool(ctx, token_a_mint, token_b_mint, pool_encryption_pubkey, fee_rate_bps)
+        instructions::initialize_pool(
+            ctx,
+            token_a_mint,
+            token_b_mint,
+            pool_encryption_pubkey,
+            fee_rate_bps,
+            curve,
+            amplification,
+        )
     }
 
     /// Initialize pool vaults (step 2: create token vaults)
@@ -56,6 +73,39 @@ pub mod darkflow {
         instructions::initialize_pool_vaults(ctx)
     }
 
+    /// Create the pool's position tree (authority only)
+    ///
+    /// Deposits append their position commitment to this tree, and its
+    /// root is the pool's state commitment.
+    pub fn initialize_position_tree(ctx: Context<InitializePositionTree>) -> Result<()> {
+        instructions::initialize_position_tree(ctx)
+    }
+
+    /// Create the pool's TWAP price oracle (authority only)
+    ///
+    /// Every trade advances the pool's cumulative prices and records an
+    /// observation here, which `consult_twap` averages over.
+    pub fn initialize_price_oracle(ctx: Context<InitializePriceOracle>) -> Result<()> {
+        instructions::initialize_price_oracle(ctx)
+    }
+
+    /// Switch an empty pool to concentrated liquidity (authority only)
+    ///
+    /// Creates the pool's public tick state. Liquidity is then provided
+    /// over tick ranges with `add_concentrated_liquidity`, and swaps only
+    /// trade against the positions whose range contains the price.
+    ///
+    /// # Arguments
+    /// * `tick_spacing` - Position bounds must be multiples of this spacing
+    /// * `initial_sqrt_price` - Square root of the starting price of token A in token B (Q64.64)
+    pub fn initialize_concentrated_liquidity(
+        ctx: Context<InitializeConcentratedLiquidity>,
+        tick_spacing: u16,
+        initial_sqrt_price: u128,
+    ) -> Result<()> {
+        instructions::initialize_concentrated_liquidity(ctx, tick_spacing, initial_sqrt_price)
+    }
+
     /// Update pool configuration (authority only)
     pub fn update_pool_config(
         ctx: Context<UpdatePoolConfig>,
@@ -65,6 +115,160 @@ pub mod darkflow {
         instructions::update_pool_config(ctx, new_fee_rate_bps, new_encryption_pubkey)
     }
 
+    /// Set the protocol's share of swap fees and its treasury (authority only)
+    ///
+    /// # Arguments
+    /// * `protocol_fee_bps` - Share of each swap fee paid to the treasury (basis points of the fee)
+    /// * `treasury` - Owner of the treasury token accounts (default disables the protocol fee)
+    pub fn set_protocol_fee(
+        ctx: Context<UpdatePoolConfig>,
+        protocol_fee_bps: u16,
+        treasury: Pubkey,
+    ) -> Result<()> {
+        instructions::set_protocol_fee(ctx, protocol_fee_bps, treasury)
+    }
+
+    /// Pause or resume trading, deposits and withdrawals (authority only)
+    pub fn set_pool_active(ctx: Context<UpdatePoolConfig>, is_active: bool) -> Result<()> {
+        instructions::set_pool_active(ctx, is_active)
+    }
+
+    /// Enter or leave emergency mode (authority only)
+    ///
+    /// Emergency mode stops swaps, orders and deposits but keeps LP
+    /// withdrawals open, even while the pool is paused.
+    pub fn set_emergency_mode(ctx: Context<UpdatePoolConfig>, emergency_mode: bool) -> Result<()> {
+        instructions::set_emergency_mode(ctx, emergency_mode)
+    }
+
+    /// Propose a new pool authority (authority only)
+    ///
+    /// Takes effect when the proposed authority calls `accept_pool_authority`.
+    /// Proposing the default key cancels a pending transfer.
+    pub fn propose_pool_authority(ctx: Context<UpdatePoolConfig>, new_authority: Pubkey) -> Result<()> {
+        instructions::propose_pool_authority(ctx, new_authority)
+    }
+
+    /// Accept a proposed pool authority transfer (pending authority only)
+    pub fn accept_pool_authority(ctx: Context<AcceptPoolAuthority>) -> Result<()> {
+        instructions::accept_pool_authority(ctx)
+    }
+
+    /// Ramp a StableSwap pool's amplification linearly (authority only)
+    ///
+    /// The ramp must last at least a day and change the amplification by at
+    /// most a factor of ten, and a new ramp can start at most once a day.
+    ///
+    /// # Arguments
+    /// * `future_amplification` - Amplification reached at the end of the ramp
+    /// * `future_time` - Unix timestamp the ramp ends at
+    pub fn ramp_amplification(
+        ctx: Context<UpdatePoolConfig>,
+        future_amplification: u64,
+        future_time: i64,
+    ) -> Result<()> {
+        instructions::ramp_amplification(ctx, future_amplification, future_time)
+    }
+
+    /// Stop an amplification ramp at its current value (authority only)
+    pub fn stop_amplification_ramp(ctx: Context<UpdatePoolConfig>) -> Result<()> {
+        instructions::stop_amplification_ramp(ctx)
+    }
+
+    /// Pay accrued protocol fees to the treasury (permissionless)
+    pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+        instructions::collect_protocol_fees(ctx)
+    }
+
+    // ========================================================================
+    // Verifying Keys
+    // ========================================================================
+
+    /// Create the verifying key registry for one of the pool's circuits (authority only)
+    pub fn initialize_verifying_key_registry(
+        ctx: Context<InitializeVerifyingKeyRegistry>,
+        circuit: CircuitKind,
+    ) -> Result<()> {
+        instructions::initialize_verifying_key_registry(ctx, circuit)
+    }
+
+    /// Upload a chunk of a new verifying key into the registry's staging buffer
+    pub fn write_verifying_key(
+        ctx: Context<WriteVerifyingKey>,
+        offset: u32,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        instructions::write_verifying_key(ctx, offset, data)
+    }
+
+    /// Activate the staged verifying key as a new version
+    ///
+    /// The replaced key stays available to pools pinned to it until they
+    /// pin the new version.
+    ///
+    /// # Arguments
+    /// * `expected_key_hash` - SHA-256 of the staged key, as exported from the circuit build
+    pub fn activate_verifying_key(
+        ctx: Context<ActivateVerifyingKey>,
+        expected_key_hash: [u8; 32],
+    ) -> Result<()> {
+        instructions::activate_verifying_key(ctx, expected_key_hash)
+    }
+
+    /// Pin the verifying key version the pool accepts for a circuit (active or previous)
+    pub fn pin_verifying_key(ctx: Context<PinVerifyingKey>, version: u32) -> Result<()> {
+        instructions::pin_verifying_key(ctx, version)
+    }
+
+    // ========================================================================
+    // Solvers
+    // ========================================================================
+
+    /// Create the solver registry for a pool (authority only)
+    ///
+    /// # Arguments
+    /// * `min_bond` - Minimum bond in lamports a solver must post to register
+    pub fn initialize_solver_registry(
+        ctx: Context<InitializeSolverRegistry>,
+        min_bond: u64,
+    ) -> Result<()> {
+        instructions::initialize_solver_registry(ctx, min_bond)
+    }
+
+    /// Register as a solver by posting a bond in lamports
+    pub fn register_solver(ctx: Context<RegisterSolver>, bond: u64) -> Result<()> {
+        instructions::register_solver(ctx, bond)
+    }
+
+    /// Activate or deactivate a registered solver (authority only)
+    pub fn set_solver_active(
+        ctx: Context<SetSolverActive>,
+        solver: Pubkey,
+        is_active: bool,
+    ) -> Result<()> {
+        instructions::set_solver_active(ctx, solver, is_active)
+    }
+
+    /// Remove a solver and return its bond (authority only)
+    pub fn remove_solver(ctx: Context<RemoveSolver>) -> Result<()> {
+        instructions::remove_solver(ctx)
+    }
+
+    /// Remove a solver and send its bond to the pool treasury (authority only)
+    pub fn slash_solver(ctx: Context<SlashSolver>, solver: Pubkey) -> Result<()> {
+        instructions::slash_solver(ctx, solver)
+    }
+
+    /// Deactivate the signing solver and start its unbonding cooldown
+    pub fn request_solver_unbond(ctx: Context<RequestSolverUnbond>) -> Result<()> {
+        instructions::request_solver_unbond(ctx)
+    }
+
+    /// Withdraw the signing solver's bond once its unbonding cooldown has passed
+    pub fn withdraw_solver_bond(ctx: Context<WithdrawSolverBond>) -> Result<()> {
+        instructions::withdraw_solver_bond(ctx)
+    }
+
     // ========================================================================
     // Encrypted Liquidity
     // ========================================================================
@@ -74,37 +278,196 @@ pub mod darkflow {
     /// The LP deposits tokens, but the exact amount is encrypted. Only the
     /// LP and the pool (via MPC) can know the actual amount.
     ///
+    /// The deposit mints LP shares proportional to the pool reserves. The
+    /// position stores only a commitment to the share amount, which is
+    /// appended to the pool's position tree.
+    ///
     /// # Arguments
     /// * `encrypted_amount` - NaCl box encrypted deposit amount
-    /// * `commitment` - Pedersen commitment to the amount (for ZK verification)
-    /// * `amount_a` - Actual token A amount (will be verified against commitment)
-    /// * `amount_b` - Actual token B amount (will be verified against commitment)
+    /// * `note_commitment` - SHA-256(secret || randomness), where only the LP knows the secret
+    /// * `amount_a` - Token A amount to deposit
+    /// * `amount_b` - Token B amount to deposit
     pub fn add_liquidity_encrypted(
         ctx: Context<AddLiquidityEncrypted>,
         encrypted_amount: Vec<u8>,
-        commitment: [u8; 32],
+        note_commitment: [u8; 32],
         amount_a: u64,
         amount_b: u64,
     ) -> Result<()> {
-        instructions::add_liquidity_encrypted(ctx, encrypted_amount, commitment, amount_a, amount_b)
+        instructions::add_liquidity_encrypted(ctx, encrypted_amount, note_commitment, amount_a, amount_b)
     }
 
     /// Remove liquidity with ZK proof of ownership
     ///
-    /// The LP provides a ZK proof that they own the position without
-    /// revealing the exact amount to anyone except themselves.
+    /// The LP provides a ZK proof that they own a position in the pool's
+    /// position tree without revealing which one, and publishes the
+    /// position's nullifier so it can only be withdrawn once. The proof is
+    /// bound to the recipient token accounts rather than to the signer, so
+    /// any wallet can submit it.
+    ///
+    /// A partial withdrawal keeps the position open: the remaining shares
+    /// are re-committed under a fresh note, appended to the position tree
+    /// and stored in `change_position`. Only a full exit closes it.
+    ///
+    /// The position's shares and fee checkpoint are private witnesses of
+    /// the proof, so nothing in the withdrawal points back at the deposit.
     ///
     /// # Arguments
-    /// * `position_commitment` - The commitment identifying the position
-    /// * `zk_proof` - Noir proof of position ownership
-    /// * `withdraw_percentage_bps` - Percentage to withdraw (in basis points)
+    /// * `merkle_root` - A recent position tree root the proof is made against
+    /// * `nullifier` - SHA-256(secret || leaf_index), bound by the proof
+    /// * `zk_proof` - Noir proof of position ownership, payout and change
+    /// * `shares_to_burn` - LP shares to withdraw, at most the position holds
+    /// * `fees_a` - Token A fees the position earned (bound by the proof)
+    /// * `fees_b` - Token B fees the position earned (bound by the proof)
+    /// * `change_commitment` - Commitment to the remaining shares (zero on full exit)
+    /// * `encrypted_change` - Encrypted data for the remaining position (empty on full exit)
+    #[allow(clippy::too_many_arguments)]
     pub fn remove_liquidity_private(
         ctx: Context<RemoveLiquidityPrivate>,
-        position_commitment: [u8; 32],
+        merkle_root: [u8; 32],
+        nullifier: [u8; 32],
+        zk_proof: Vec<u8>,
+        shares_to_burn: u64,
+        fees_a: u64,
+        fees_b: u64,
+        change_commitment: [u8; 32],
+        encrypted_change: Vec<u8>,
+    ) -> Result<()> {
+        instructions::remove_liquidity_private(
+            ctx,
+            merkle_root,
+            nullifier,
+            zk_proof,
+            shares_to_burn,
+            fees_a,
+            fees_b,
+            change_commitment,
+            encrypted_change,
+        )
+    }
+
+    /// Claim the LP fees a position has earned, keeping its principal
+    ///
+    /// Fees accrue per share from every swap. The position is spent with a
+    /// position proof and its shares are re-committed with the current fee
+    /// growth as the new checkpoint.
+    ///
+    /// # Arguments
+    /// * `merkle_root` - A recent position tree root the proof is made against
+    /// * `nullifier` - SHA-256(secret || leaf_index), bound by the proof
+    /// * `zk_proof` - Noir proof of position ownership, payout and change
+    /// * `fees_a` - Token A fees the position earned (bound by the proof)
+    /// * `fees_b` - Token B fees the position earned (bound by the proof)
+    /// * `change_commitment` - Commitment to the same shares at the current fee growth
+    /// * `encrypted_change` - Encrypted data for the re-committed position
+    #[allow(clippy::too_many_arguments)]
+    pub fn claim_lp_fees(
+        ctx: Context<ClaimLpFees>,
+        merkle_root: [u8; 32],
+        nullifier: [u8; 32],
+        zk_proof: Vec<u8>,
+        fees_a: u64,
+        fees_b: u64,
+        change_commitment: [u8; 32],
+        encrypted_change: Vec<u8>,
+    ) -> Result<()> {
+        instructions::claim_lp_fees(
+            ctx,
+            merkle_root,
+            nullifier,
+            zk_proof,
+            fees_a,
+            fees_b,
+            change_commitment,
+            encrypted_change,
+        )
+    }
+
+    /// Add concentrated liquidity over a tick range
+    ///
+    /// Only for pools switched to concentrated liquidity. The LP supplies
+    /// `liquidity` between `tick_lower` and `tick_upper`; the range and size
+    /// are committed in the position rather than stored, while the per-tick
+    /// liquidity the swaps need is aggregated publicly.
+    ///
+    /// # Arguments
+    /// * `encrypted_amount` - NaCl box encrypted position data (range, liquidity)
+    /// * `note_commitment` - SHA-256(secret || randomness), where only the LP knows the secret
+    /// * `tick_lower` - Lower bound of the range (multiple of the tick spacing)
+    /// * `tick_upper` - Upper bound of the range (multiple of the tick spacing)
+    /// * `liquidity` - Liquidity to add over the range, at least `MIN_POSITION_LIQUIDITY`
+    /// * `max_amount_a` - Most token A the deposit may take
+    /// * `max_amount_b` - Most token B the deposit may take
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_concentrated_liquidity(
+        ctx: Context<AddConcentratedLiquidity>,
+        encrypted_amount: Vec<u8>,
+        note_commitment: [u8; 32],
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u64,
+        max_amount_a: u64,
+        max_amount_b: u64,
+    ) -> Result<()> {
+        instructions::add_concentrated_liquidity(
+            ctx,
+            encrypted_amount,
+            note_commitment,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            max_amount_a,
+            max_amount_b,
+        )
+    }
+
+    /// Remove concentrated liquidity with ZK proof of ownership
+    ///
+    /// Like `remove_liquidity_private`, with the position's liquidity in
+    /// place of shares. The proof binds the range, and fees are paid from
+    /// the fee growth inside it. The range is public, since the tick
+    /// updates reveal it; the position's liquidity and fee checkpoint are
+    /// private witnesses of the proof.
+    ///
+    /// # Arguments
+    /// * `merkle_root` - A recent position tree root the proof is made against
+    /// * `nullifier` - SHA-256(secret || leaf_index), bound by the proof
+    /// * `zk_proof` - Noir proof of position ownership, payout and change
+    /// * `tick_lower` - Lower bound of the position's range (bound by the proof)
+    /// * `tick_upper` - Upper bound of the position's range (bound by the proof)
+    /// * `liquidity_to_burn` - Liquidity to withdraw, at most the position holds
+    /// * `fees_a` - Token A fees earned inside the range (bound by the proof)
+    /// * `fees_b` - Token B fees earned inside the range (bound by the proof)
+    /// * `change_commitment` - Commitment to the remaining liquidity (zero on full exit);
+    ///   the remaining liquidity must be at least `MIN_POSITION_LIQUIDITY`
+    /// * `encrypted_change` - Encrypted data for the remaining position (empty on full exit)
+    #[allow(clippy::too_many_arguments)]
+    pub fn remove_concentrated_liquidity(
+        ctx: Context<RemoveConcentratedLiquidity>,
+        merkle_root: [u8; 32],
+        nullifier: [u8; 32],
         zk_proof: Vec<u8>,
-        withdraw_percentage_bps: u16,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity_to_burn: u64,
+        fees_a: u64,
+        fees_b: u64,
+        change_commitment: [u8; 32],
+        encrypted_change: Vec<u8>,
     ) -> Result<()> {
-        instructions::remove_liquidity_private(ctx, position_commitment, zk_proof, withdraw_percentage_bps)
+        instructions::remove_concentrated_liquidity(
+            ctx,
+            merkle_root,
+            nullifier,
+            zk_proof,
+            tick_lower,
+            tick_upper,
+            liquidity_to_burn,
+            fees_a,
+            fees_b,
+            change_commitment,
+            encrypted_change,
+        )
     }
 
     // ========================================================================
@@ -122,37 +485,72 @@ pub mod darkflow {
     /// * `encrypted_order` - Encrypted swap parameters (amount, min_output, deadline)
     /// * `zk_proof` - Noir proof of swap validity
     /// * `nullifier` - Prevents replay attacks
+    /// * `input_amount` - Input amount, bound as a public input of the proof
+    /// * `min_output_amount` - Minimum output, bound as a public input of the proof
     pub fn dark_swap(
         ctx: Context<DarkSwap>,
         encrypted_order: Vec<u8>,
         zk_proof: Vec<u8>,
         nullifier: [u8; 32],
+        input_amount: u64,
+        min_output_amount: u64,
     ) -> Result<()> {
-        instructions::dark_swap(ctx, encrypted_order, zk_proof, nullifier)
+        instructions::dark_swap(ctx, encrypted_order, zk_proof, nullifier, input_amount, min_output_amount)
     }
 
     /// Submit a dark order for later execution by solver
     ///
     /// Order details are encrypted for the solver. Only the solver can
-    /// decrypt and execute the order.
+    /// decrypt and execute the order. `commitment` is
+    /// SHA-256(min_output_le || slippage_bps_le || salt), opened by the
+    /// solver at execution. The input is held in a per-order
+    /// escrow owned by the pool until the order is filled or cancelled.
+    ///
+    /// # Arguments
+    /// * `direction` - Which pool mint is sold; fixes the mint the maker receives
     pub fn submit_dark_order(
         ctx: Context<SubmitDarkOrder>,
         encrypted_params: Vec<u8>,
         commitment: [u8; 32],
         input_amount: u64,
         deadline: i64,
+        direction: SwapDirection,
     ) -> Result<()> {
-        instructions::submit_dark_order(ctx, encrypted_params, commitment, input_amount, deadline)
+        instructions::submit_dark_order(ctx, encrypted_params, commitment, input_amount, deadline, direction)
     }
 
-    /// Execute a dark order (solver only)
+    /// Execute a dark order (active registered solvers only)
+    ///
+    /// # Arguments
+    /// * `reveal` - Decrypted order parameters; must open the order commitment
     pub fn execute_dark_order(
         ctx: Context<ExecuteDarkOrder>,
-        decrypted_min_output: u64,
+        reveal: OrderReveal,
         execution_proof: Vec<u8>,
         encrypted_output: Vec<u8>,
     ) -> Result<()> {
-        instructions::execute_dark_order(ctx, decrypted_min_output, execution_proof, encrypted_output)
+        instructions::execute_dark_order(ctx, reveal, execution_proof, encrypted_output)
+    }
+
+    /// Settle a batch of pending dark orders at one uniform clearing price
+    /// (active registered solvers only)
+    ///
+    /// Opposite directions are netted against each other and only the
+    /// residual trades against the pool reserves, so fill order inside the
+    /// batch carries no information and cannot be sequenced by the solver.
+    ///
+    /// Remaining accounts, per order: `order`, `escrow_token`,
+    /// `maker_input_token` (receives anything donated to the escrow),
+    /// `maker_output_token`, `maker`.
+    ///
+    /// # Arguments
+    /// * `reveals` - Decrypted parameters of each order, in order; each must
+    ///   open its order commitment
+    pub fn settle_dark_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettleDarkBatch<'info>>,
+        reveals: Vec<OrderReveal>,
+    ) -> Result<()> {
+        instructions::settle_dark_batch(ctx, reveals)
     }
 
     /// Cancel a pending dark order
@@ -160,32 +558,101 @@ pub mod darkflow {
         instructions::cancel_dark_order(ctx)
     }
 
+    /// Expire a pending dark order past its deadline (permissionless crank)
+    ///
+    /// Refunds the escrow to the maker and closes the order. The caller
+    /// receives a small reward out of the order account's rent.
+    pub fn expire_dark_order(ctx: Context<ExpireDarkOrder>) -> Result<()> {
+        instructions::expire_dark_order(ctx)
+    }
+
     // ========================================================================
     // Confidential Token Launch
     // ========================================================================
 
-    /// Launch a token with a confidential bonding curve
+    /// Launch a token on a public bonding curve with confidential purchases
     ///
-    /// The bonding curve parameters are encrypted. Early buyers cannot
-    /// see how much others have purchased, preventing front-running.
+    /// The curve, the raise and the supply sold are public, so buyers can
+    /// price their purchases; each buyer's holdings are kept only behind a
+    /// commitment on their purchase record.
+    ///
+    /// # Arguments
+    /// * `curve` - Bonding curve evaluated on-chain
+    /// * `start_time` - Sale start timestamp (0 = now)
+    /// * `end_time` - Sale end timestamp (0 = no end)
+    /// * `caps` - Soft cap, hard cap and per-wallet limit in payment units
+    #[allow(clippy::too_many_arguments)]
     pub fn launch_confidential_token(
         ctx: Context<LaunchConfidentialToken>,
         token_mint: Pubkey,
-        encrypted_curve_params: Vec<u8>,
+        curve: CurveParams,
         initial_price: u64,
         max_supply: u64,
+        start_time: i64,
+        end_time: i64,
+        caps: LaunchCaps,
     ) -> Result<()> {
-        instructions::launch_confidential_token(ctx, token_mint, encrypted_curve_params, initial_price, max_supply)
+        instructions::launch_confidential_token(
+            ctx,
+            token_mint,
+            curve,
+            initial_price,
+            max_supply,
+            start_time,
+            end_time,
+            caps,
+        )
     }
 
     /// Buy tokens from confidential launch
+    ///
+    /// # Arguments
+    /// * `encrypted_amount` - ElGamal ciphertexts of the amount's low and high
+    ///   32 bits under the buyer's key, added to the buyer's purchase record
+    /// * `totals` - Reveal of the buyer's totals before this purchase (zero
+    ///   on the first purchase)
+    /// * `salt` - Fresh blinding salt for the commitment to the new totals
+    /// * `payment_amount` - Most the buyer will spend
+    ///
+    /// The purchase account keeps only the ciphertext and a commitment to
+    /// the totals, never the amounts themselves.
     pub fn buy_from_launch(
         ctx: Context<BuyFromLaunch>,
         encrypted_amount: Vec<u8>,
-        commitment: [u8; 32],
+        totals: PurchaseReveal,
+        salt: [u8; 32],
         payment_amount: u64,
     ) -> Result<()> {
-        instructions::buy_from_launch(ctx, encrypted_amount, commitment, payment_amount)
+        instructions::buy_from_launch(ctx, encrypted_amount, totals, salt, payment_amount)
+    }
+
+    /// Claim the tokens bought from a launch once the sale is over
+    ///
+    /// `totals` must open the purchase commitment; its token amount is paid.
+    pub fn claim_launch_tokens(ctx: Context<ClaimLaunchTokens>, totals: PurchaseReveal) -> Result<()> {
+        instructions::claim_launch_tokens(ctx, totals)
+    }
+
+    /// End an active launch early (creator only)
+    pub fn end_launch(ctx: Context<EndLaunch>) -> Result<()> {
+        instructions::end_launch(ctx)
+    }
+
+    /// Cancel a launch and return the tokens for sale to the creator
+    pub fn cancel_launch(ctx: Context<CancelLaunch>) -> Result<()> {
+        instructions::cancel_launch(ctx)
+    }
+
+    /// Refund a purchase from a cancelled launch or one that missed its soft cap
+    ///
+    /// `totals` must open the purchase commitment; its payment amount is paid.
+    pub fn refund_launch_purchase(ctx: Context<RefundLaunchPurchase>, totals: PurchaseReveal) -> Result<()> {
+        instructions::refund_launch_purchase(ctx, totals)
+    }
+
+    /// Withdraw payments and unsold tokens once the sale is over
+    pub fn withdraw_launch_proceeds(ctx: Context<WithdrawLaunchProceeds>) -> Result<()> {
+        instructions::withdraw_launch_proceeds(ctx)
     }
 
     // ========================================================================
@@ -194,9 +661,20 @@ pub mod darkflow {
 
     /// Get pool aggregates (public data)
     /// Individual positions remain private
+    ///
+    /// TVL and spot price are read from the pool vaults, and volume is the
+    /// rolling 24-hour window kept on the pool.
     pub fn query_pool_aggregates(ctx: Context<QueryPoolAggregates>) -> Result<PoolAggregates> {
         instructions::query_pool_aggregates(ctx)
     }
+
+    /// Get the time-weighted average price over at least `window` seconds
+    ///
+    /// Individual trades are hidden, so the TWAP is the public price signal
+    /// other programs can consume.
+    pub fn consult_twap(ctx: Context<ConsultTwap>, window: u32) -> Result<TwapQuote> {
+        instructions::consult_twap(ctx, window)
+    }
 }
 
 // ============================================================================
@@ -265,58 +743,83 @@ pub struct InitializePoolVaults<'info> {
 }
 
 #[derive(Accounts)]
-pub struct UpdatePoolConfig<'info> {
+pub struct InitializePositionTree<'info> {
     #[account(mut, has_one = authority)]
     pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PositionTree::INIT_SPACE,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(mut)]
     pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct AddLiquidityEncrypted<'info> {
-    #[account(mut)]
+pub struct InitializePriceOracle<'info> {
+    #[account(has_one = authority)]
     pub pool: Account<'info, DarkPool>,
 
     #[account(
         init,
-        payer = lp,
-        space = 8 + EncryptedPosition::INIT_SPACE,
-        seeds = [b"position", pool.key().as_ref(), lp.key().as_ref(), &pool.position_count.to_le_bytes()],
+        payer = authority,
+        space = 8 + PriceOracle::INIT_SPACE,
+        seeds = [b"price_oracle", pool.key().as_ref()],
         bump
     )]
-    pub position: Account<'info, EncryptedPosition>,
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
 
     #[account(mut)]
-    pub lp_token_a: Account<'info, TokenAccount>,
+    pub authority: Signer<'info>,
 
-    #[account(mut)]
-    pub lp_token_b: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
 
-    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
-    pub vault_a: Account<'info, TokenAccount>,
+#[derive(Accounts)]
+pub struct InitializeConcentratedLiquidity<'info> {
+    #[account(mut, has_one = authority)]
+    pub pool: Account<'info, DarkPool>,
 
-    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
-    pub vault_b: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PoolTicks::INIT_SPACE,
+        seeds = [b"pool_ticks", pool.key().as_ref()],
+        bump
+    )]
+    pub pool_ticks: Box<Account<'info, PoolTicks>>,
 
     #[account(mut)]
-    pub lp: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct RemoveLiquidityPrivate<'info> {
-    #[account(mut)]
+pub struct UpdatePoolConfig<'info> {
+    #[account(mut, has_one = authority)]
     pub pool: Account<'info, DarkPool>,
+    pub authority: Signer<'info>,
+}
 
-    #[account(mut, has_one = owner, close = owner)]
-    pub position: Account<'info, EncryptedPosition>,
-
+#[derive(Accounts)]
+pub struct AcceptPoolAuthority<'info> {
     #[account(mut)]
-    pub owner_token_a: Account<'info, TokenAccount>,
+    pub pool: Account<'info, DarkPool>,
+    pub pending_authority: Signer<'info>,
+}
 
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
     #[account(mut)]
-    pub owner_token_b: Account<'info, TokenAccount>,
+    pub pool: Account<'info, DarkPool>,
 
     #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
     pub vault_a: Account<'info, TokenAccount>,
@@ -324,153 +827,961 @@ pub struct RemoveLiquidityPrivate<'info> {
     #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
     pub vault_b: Account<'info, TokenAccount>,
 
-    pub owner: Signer<'info>,
-
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-#[instruction(encrypted_order: Vec<u8>, zk_proof: Vec<u8>, nullifier: [u8; 32])]
-pub struct DarkSwap<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, DarkPool>,
-
     #[account(mut)]
-    pub user_input_token: Account<'info, TokenAccount>,
+    pub treasury_token_a: Account<'info, TokenAccount>,
 
     #[account(mut)]
-    pub user_output_token: Account<'info, TokenAccount>,
+    pub treasury_token_b: Account<'info, TokenAccount>,
 
-    #[account(mut)]
-    pub vault_input: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
 
-    #[account(mut)]
-    pub vault_output: Account<'info, TokenAccount>,
+#[derive(Accounts)]
+#[instruction(circuit: CircuitKind)]
+pub struct InitializeVerifyingKeyRegistry<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
 
     #[account(
         init,
-        payer = user,
-        space = 8 + NullifierAccount::INIT_SPACE,
-        seeds = [b"nullifier", pool.key().as_ref(), nullifier.as_ref()],
+        payer = authority,
+        space = 8 + VerifyingKeyRegistry::INIT_SPACE,
+        seeds = [b"vk_registry", pool.key().as_ref(), &[circuit as u8]],
         bump
     )]
-    pub nullifier_account: Account<'info, NullifierAccount>,
+    pub registry: Box<Account<'info, VerifyingKeyRegistry>>,
 
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct SubmitDarkOrder<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, DarkPool>,
-
-    #[account(
-        init,
-        payer = maker,
-        space = 8 + DarkOrder::INIT_SPACE,
-        seeds = [b"dark_order", pool.key().as_ref(), maker.key().as_ref(), &pool.order_count.to_le_bytes()],
-        bump
-    )]
-    pub order: Account<'info, DarkOrder>,
-
-    #[account(mut)]
-    pub maker_input_token: Account<'info, TokenAccount>,
-
-    #[account(mut)]
-    pub escrow_token: Account<'info, TokenAccount>,
+pub struct WriteVerifyingKey<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
 
-    #[account(mut)]
-    pub maker: Signer<'info>,
+    #[account(mut, has_one = pool)]
+    pub registry: Box<Account<'info, VerifyingKeyRegistry>>,
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ExecuteDarkOrder<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, DarkPool>,
+pub struct ActivateVerifyingKey<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
 
     #[account(mut, has_one = pool)]
-    pub order: Account<'info, DarkOrder>,
+    pub registry: Box<Account<'info, VerifyingKeyRegistry>>,
 
-    pub solver: Signer<'info>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PinVerifyingKey<'info> {
+    #[account(mut, has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(has_one = pool)]
+    pub registry: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSolverRegistry<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + SolverRegistry::INIT_SPACE,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSolver<'info> {
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    #[account(mut)]
+    pub solver: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSolverActive<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveSolver<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    /// CHECK: Solver being removed; receives its bond back
+    #[account(mut)]
+    pub solver: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SlashSolver<'info> {
+    #[account(has_one = authority)]
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    /// CHECK: Pool treasury; receives the slashed bond
+    #[account(mut, address = pool.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RequestSolverUnbond<'info> {
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    pub solver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSolverBond<'info> {
+    pub pool: Box<Account<'info, DarkPool>>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = registry.bump
+    )]
+    pub registry: Box<Account<'info, SolverRegistry>>,
+
+    #[account(mut)]
+    pub solver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddLiquidityEncrypted<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        init,
+        payer = lp,
+        space = 8 + EncryptedPosition::INIT_SPACE,
+        seeds = [b"position", pool.key().as_ref(), &position_tree.next_index.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, EncryptedPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump = position_tree.bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(mut)]
+    pub lp_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_token_b: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(merkle_root: [u8; 32], nullifier: [u8; 32])]
+pub struct RemoveLiquidityPrivate<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        mut,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump = position_tree.bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(
+        init,
+        payer = withdrawer,
+        space = 8 + NullifierAccount::INIT_SPACE,
+        seeds = [b"position_nullifier", pool.key().as_ref(), nullifier.as_ref()],
+        bump
+    )]
+    pub nullifier_account: Account<'info, NullifierAccount>,
+
+    /// Position holding the remaining shares; required for partial withdrawals
+    #[account(
+        init,
+        payer = withdrawer,
+        space = 8 + EncryptedPosition::INIT_SPACE,
+        seeds = [b"position", pool.key().as_ref(), &position_tree.next_index.to_le_bytes()],
+        bump
+    )]
+    pub change_position: Option<Account<'info, EncryptedPosition>>,
+
+    #[account(
+        seeds = [b"vk_registry", pool.key().as_ref(), &[CircuitKind::Position as u8]],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    #[account(mut, token::mint = pool.token_a_mint)]
+    pub recipient_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.token_b_mint)]
+    pub recipient_token_b: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    /// Pays for the nullifier account; need not be the position owner
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(merkle_root: [u8; 32], nullifier: [u8; 32])]
+pub struct ClaimLpFees<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        mut,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump = position_tree.bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(
+        init,
+        payer = claimer,
+        space = 8 + NullifierAccount::INIT_SPACE,
+        seeds = [b"position_nullifier", pool.key().as_ref(), nullifier.as_ref()],
+        bump
+    )]
+    pub nullifier_account: Account<'info, NullifierAccount>,
+
+    /// Position holding the re-committed shares
+    #[account(
+        init,
+        payer = claimer,
+        space = 8 + EncryptedPosition::INIT_SPACE,
+        seeds = [b"position", pool.key().as_ref(), &position_tree.next_index.to_le_bytes()],
+        bump
+    )]
+    pub change_position: Account<'info, EncryptedPosition>,
+
+    #[account(
+        seeds = [b"vk_registry", pool.key().as_ref(), &[CircuitKind::Position as u8]],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    #[account(mut, token::mint = pool.token_a_mint)]
+    pub recipient_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.token_b_mint)]
+    pub recipient_token_b: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    /// Pays for the nullifier and position accounts; need not be the position owner
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddConcentratedLiquidity<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        init,
+        payer = lp,
+        space = 8 + EncryptedPosition::INIT_SPACE,
+        seeds = [b"position", pool.key().as_ref(), &position_tree.next_index.to_le_bytes()],
+        bump
+    )]
+    pub position: Account<'info, EncryptedPosition>,
+
+    #[account(
+        mut,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump = position_tree.bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_ticks", pool.key().as_ref()],
+        bump = pool_ticks.bump
+    )]
+    pub pool_ticks: Box<Account<'info, PoolTicks>>,
+
+    #[account(mut)]
+    pub lp_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp_token_b: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub lp: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(merkle_root: [u8; 32], nullifier: [u8; 32])]
+pub struct RemoveConcentratedLiquidity<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        mut,
+        seeds = [b"position_tree", pool.key().as_ref()],
+        bump = position_tree.bump
+    )]
+    pub position_tree: Box<Account<'info, PositionTree>>,
+
+    #[account(
+        mut,
+        seeds = [b"pool_ticks", pool.key().as_ref()],
+        bump = pool_ticks.bump
+    )]
+    pub pool_ticks: Box<Account<'info, PoolTicks>>,
+
+    #[account(
+        init,
+        payer = withdrawer,
+        space = 8 + NullifierAccount::INIT_SPACE,
+        seeds = [b"position_nullifier", pool.key().as_ref(), nullifier.as_ref()],
+        bump
+    )]
+    pub nullifier_account: Account<'info, NullifierAccount>,
+
+    /// Position holding the remaining liquidity; required for partial withdrawals
+    #[account(
+        init,
+        payer = withdrawer,
+        space = 8 + EncryptedPosition::INIT_SPACE,
+        seeds = [b"position", pool.key().as_ref(), &position_tree.next_index.to_le_bytes()],
+        bump
+    )]
+    pub change_position: Option<Account<'info, EncryptedPosition>>,
+
+    #[account(
+        seeds = [b"vk_registry", pool.key().as_ref(), &[CircuitKind::Position as u8]],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    #[account(mut, token::mint = pool.token_a_mint)]
+    pub recipient_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = pool.token_b_mint)]
+    pub recipient_token_b: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    /// Pays for the nullifier account; need not be the position owner
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(encrypted_order: Vec<u8>, zk_proof: Vec<u8>, nullifier: [u8; 32])]
+pub struct DarkSwap<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(mut)]
+    pub user_input_token: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_output_token: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+
+    /// Tick state; required for concentrated liquidity pools
+    #[account(
+        mut,
+        seeds = [b"pool_ticks", pool.key().as_ref()],
+        bump = pool_ticks.bump
+    )]
+    pub pool_ticks: Option<Box<Account<'info, PoolTicks>>>,
+
+    #[account(
+        seeds = [b"vk_registry", pool.key().as_ref(), &[CircuitKind::Swap as u8]],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + NullifierAccount::INIT_SPACE,
+        seeds = [b"nullifier", pool.key().as_ref(), nullifier.as_ref()],
+        bump
+    )]
+    pub nullifier_account: Account<'info, NullifierAccount>,
 
     #[account(mut)]
-    pub escrow_token: Account<'info, TokenAccount>,
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+pub struct SubmitDarkOrder<'info> {
     #[account(mut)]
-    pub maker_output_token: Account<'info, TokenAccount>,
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        init,
+        payer = maker,
+        space = 8 + DarkOrder::INIT_SPACE,
+        seeds = [b"dark_order", pool.key().as_ref(), maker.key().as_ref(), &pool.order_count.to_le_bytes()],
+        bump
+    )]
+    pub order: Account<'info, DarkOrder>,
+
+    pub input_mint: Box<Account<'info, Mint>>,
 
     #[account(mut)]
-    pub vault_input: Account<'info, TokenAccount>,
+    pub maker_input_token: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = maker,
+        token::mint = input_mint,
+        token::authority = pool,
+        seeds = [b"order_escrow", order.key().as_ref()],
+        bump
+    )]
+    pub escrow_token: Box<Account<'info, TokenAccount>>,
 
     #[account(mut)]
-    pub vault_output: Account<'info, TokenAccount>,
+    pub maker: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CancelDarkOrder<'info> {
-    #[account(mut, has_one = maker, close = maker)]
+pub struct ExecuteDarkOrder<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(mut, has_one = pool)]
     pub order: Account<'info, DarkOrder>,
 
+    pub solver: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = solver_registry.bump
+    )]
+    pub solver_registry: Box<Account<'info, SolverRegistry>>,
+
+    #[account(
+        seeds = [b"vk_registry", pool.key().as_ref(), &[CircuitKind::Execution as u8]],
+        bump = verifying_key.bump
+    )]
+    pub verifying_key: Box<Account<'info, VerifyingKeyRegistry>>,
+
+    #[account(mut, seeds = [b"order_escrow", order.key().as_ref()], bump)]
+    pub escrow_token: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = maker_output_token.owner == order.maker @ DarkFlowError::Unauthorized,
+        constraint = maker_output_token.mint == order.output_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub maker_output_token: Account<'info, TokenAccount>,
+
+    /// Receives anything donated to the escrow beyond the order amount
+    #[account(
+        mut,
+        constraint = maker_input_token.owner == order.maker @ DarkFlowError::Unauthorized,
+        constraint = maker_input_token.mint == order.input_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub maker_input_token: Account<'info, TokenAccount>,
+
+    /// CHECK: Order maker; receives the escrow account rent
+    #[account(mut, address = order.maker)]
+    pub maker: UncheckedAccount<'info>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+
+    /// Tick state; required for concentrated liquidity pools
+    #[account(
+        mut,
+        seeds = [b"pool_ticks", pool.key().as_ref()],
+        bump = pool_ticks.bump
+    )]
+    pub pool_ticks: Option<Box<Account<'info, PoolTicks>>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SettleDarkBatch<'info> {
     #[account(mut)]
+    pub pool: Account<'info, DarkPool>,
+
+    pub solver: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [b"solver_registry", pool.key().as_ref()],
+        bump = solver_registry.bump
+    )]
+    pub solver_registry: Box<Account<'info, SolverRegistry>>,
+
+    #[account(mut, seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut, seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CancelDarkOrder<'info> {
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(mut, has_one = maker, has_one = pool, close = maker)]
+    pub order: Account<'info, DarkOrder>,
+
+    #[account(mut, seeds = [b"order_escrow", order.key().as_ref()], bump)]
     pub escrow_token: Account<'info, TokenAccount>,
 
     #[account(mut)]
     pub maker_input_token: Account<'info, TokenAccount>,
 
+    #[account(mut)]
     pub maker: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct ExpireDarkOrder<'info> {
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(mut, has_one = maker, has_one = pool, close = maker)]
+    pub order: Account<'info, DarkOrder>,
+
+    #[account(mut, seeds = [b"order_escrow", order.key().as_ref()], bump)]
+    pub escrow_token: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = maker_input_token.owner == order.maker @ DarkFlowError::Unauthorized,
+        constraint = maker_input_token.mint == order.input_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub maker_input_token: Account<'info, TokenAccount>,
+
+    /// CHECK: Order maker; receives the refund and remaining rent
+    #[account(mut)]
+    pub maker: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct LaunchConfidentialToken<'info> {
     #[account(
         init,
         payer = creator,
-        space = 8 + ConfidentialLaunch::INIT_SPACE
+        space = 8 + ConfidentialLaunch::INIT_SPACE,
+        seeds = [b"launch", creator.key().as_ref(), token_mint.key().as_ref()],
+        bump
     )]
-    pub launch: Account<'info, ConfidentialLaunch>,
+    pub launch: Box<Account<'info, ConfidentialLaunch>>,
 
-    /// CHECK: Token mint for the launch
-    pub token_mint: UncheckedAccount<'info>,
+    pub token_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = creator,
+        token::mint = token_mint,
+        token::authority = launch,
+        seeds = [b"launch_token_vault", launch.key().as_ref()],
+        bump
+    )]
+    pub token_vault: Box<Account<'info, TokenAccount>>,
+
+    pub payment_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = creator,
+        token::mint = payment_mint,
+        token::authority = launch,
+        seeds = [b"launch_payment_vault", launch.key().as_ref()],
+        bump
+    )]
+    pub launch_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = creator_token.mint == token_mint.key() @ DarkFlowError::InvalidTokenMint
+    )]
+    pub creator_token: Box<Account<'info, TokenAccount>>,
 
     #[account(mut)]
     pub creator: Signer<'info>,
 
+    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct BuyFromLaunch<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
     pub launch: Account<'info, ConfidentialLaunch>,
 
-    #[account(mut)]
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + ConfidentialPurchase::INIT_SPACE,
+        seeds = [b"purchase", launch.key().as_ref(), buyer.key().as_ref()],
+        bump
+    )]
+    pub purchase: Account<'info, ConfidentialPurchase>,
+
+    #[account(
+        mut,
+        constraint = buyer_payment_token.mint == launch.payment_mint @ DarkFlowError::InvalidTokenMint
+    )]
     pub buyer_payment_token: Account<'info, TokenAccount>,
 
+    #[account(
+        mut,
+        token::mint = launch.payment_mint,
+        token::authority = launch,
+        seeds = [b"launch_payment_vault", launch.key().as_ref()],
+        bump = launch.payment_vault_bump
+    )]
+    pub launch_vault: Account<'info, TokenAccount>,
+
     #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimLaunchTokens<'info> {
+    #[account(
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
+    pub launch: Account<'info, ConfidentialLaunch>,
+
+    #[account(
+        mut,
+        has_one = launch,
+        has_one = buyer,
+        seeds = [b"purchase", launch.key().as_ref(), buyer.key().as_ref()],
+        bump = purchase.bump
+    )]
+    pub purchase: Account<'info, ConfidentialPurchase>,
+
+    #[account(
+        mut,
+        seeds = [b"launch_token_vault", launch.key().as_ref()],
+        bump = launch.token_vault_bump
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_receive_token.mint == launch.token_mint @ DarkFlowError::InvalidTokenMint
+    )]
     pub buyer_receive_token: Account<'info, TokenAccount>,
 
-    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct EndLaunch<'info> {
+    #[account(
+        mut,
+        has_one = creator,
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
+    pub launch: Account<'info, ConfidentialLaunch>,
+
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLaunch<'info> {
+    #[account(
+        mut,
+        has_one = creator,
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
+    pub launch: Account<'info, ConfidentialLaunch>,
+
+    #[account(
+        mut,
+        seeds = [b"launch_token_vault", launch.key().as_ref()],
+        bump = launch.token_vault_bump
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = creator_token.mint == launch.token_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub creator_token: Account<'info, TokenAccount>,
+
+    pub creator: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RefundLaunchPurchase<'info> {
+    #[account(
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
+    pub launch: Account<'info, ConfidentialLaunch>,
+
+    #[account(
+        mut,
+        has_one = launch,
+        has_one = buyer,
+        close = buyer,
+        seeds = [b"purchase", launch.key().as_ref(), buyer.key().as_ref()],
+        bump = purchase.bump
+    )]
+    pub purchase: Account<'info, ConfidentialPurchase>,
+
+    #[account(
+        mut,
+        seeds = [b"launch_payment_vault", launch.key().as_ref()],
+        bump = launch.payment_vault_bump
+    )]
     pub launch_vault: Account<'info, TokenAccount>,
 
+    #[account(
+        mut,
+        constraint = buyer_payment_token.mint == launch.payment_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub buyer_payment_token: Account<'info, TokenAccount>,
+
     #[account(mut)]
     pub buyer: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct WithdrawLaunchProceeds<'info> {
+    #[account(
+        mut,
+        has_one = creator,
+        seeds = [b"launch", launch.creator.as_ref(), launch.token_mint.as_ref()],
+        bump = launch.bump
+    )]
+    pub launch: Account<'info, ConfidentialLaunch>,
+
+    #[account(
+        mut,
+        seeds = [b"launch_payment_vault", launch.key().as_ref()],
+        bump = launch.payment_vault_bump
+    )]
+    pub launch_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"launch_token_vault", launch.key().as_ref()],
+        bump = launch.token_vault_bump
+    )]
+    pub token_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = creator_payment_token.mint == launch.payment_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub creator_payment_token: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = creator_token.mint == launch.token_mint @ DarkFlowError::InvalidTokenMint
+    )]
+    pub creator_token: Account<'info, TokenAccount>,
+
+    pub creator: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct QueryPoolAggregates<'info> {
     pub pool: Account<'info, DarkPool>,
+
+    #[account(seeds = [b"pool_vault_a", pool.key().as_ref()], bump)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(seeds = [b"pool_vault_b", pool.key().as_ref()], bump)]
+    pub vault_b: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ConsultTwap<'info> {
+    pub pool: Account<'info, DarkPool>,
+
+    #[account(
+        seeds = [b"price_oracle", pool.key().as_ref()],
+        bump = price_oracle.bump
+    )]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
 }
 
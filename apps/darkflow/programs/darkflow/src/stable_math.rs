@@ -0,0 +1,247 @@
+//! StableSwap invariant for two-token pools.
+//!
+//! Follows Curve's StableSwap with `n = 2` tokens: the balances `x`, `y`
+//! and the invariant `D` satisfy
+//!   `A·n^n·(x + y) + D = A·n^n·D + D^(n+1) / (n^n·x·y)`
+//! where `A` is the amplification coefficient. A large `A` keeps the curve
+//! nearly flat around the balanced point, so like-priced tokens trade with
+//! little slippage; as the pool drifts out of balance it degrades towards
+//! the constant product. `D` and post-trade balances are found by Newton's
+//! method, as in Curve.
+//!
+//! Balances are compared one to one, so both mints should share decimals.
+
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+use crate::tick_math::{mul_div, mul_div_rem};
+
+/// Largest amplification coefficient a pool may use
+pub const MAX_AMPLIFICATION: u64 = 1_000_000;
+
+/// Largest factor a single ramp may change the amplification by
+pub const MAX_AMPLIFICATION_CHANGE: u64 = 10;
+
+/// Shortest amplification ramp, and the least time between ramp starts
+pub const MIN_RAMP_DURATION: i64 = 86_400;
+
+/// Number of tokens in a pool
+const N_COINS: u128 = 2;
+
+/// Newton iterations before giving up
+const MAX_ITERATIONS: usize = 255;
+
+/// Invariant `D` of the balances
+pub fn compute_d(balance_x: u64, balance_y: u64, amplification: u64) -> Result<u128> {
+    let (x, y) = (balance_x as u128, balance_y as u128);
+    let sum = x + y;
+    if sum == 0 {
+        return Ok(0);
+    }
+    require!(x > 0 && y > 0, DarkFlowError::InsufficientBalance);
+
+    let ann = amplification as u128 * N_COINS;
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        let d_p = d_product(d, x, y)?;
+        let previous = d;
+
+        // D = (Ann·S + n·D_P)·D / ((Ann - 1)·D + (n + 1)·D_P)
+        let numerator = checked(ann.checked_mul(sum), d_p.checked_mul(N_COINS))?;
+        let denominator = checked(
+            (ann - 1).checked_mul(d),
+            d_p.checked_mul(N_COINS + 1),
+        )?;
+        d = mul_div(numerator, d, denominator)?;
+
+        if converged(d, previous) {
+            return Ok(d.max(previous));
+        }
+    }
+
+    err!(DarkFlowError::InvariantNotConverged)
+}
+
+/// Balance of the other token that keeps the invariant at `d` once one
+/// token's balance is `balance_x`
+pub fn compute_y(balance_x: u128, d: u128, amplification: u64) -> Result<u128> {
+    require!(balance_x > 0, DarkFlowError::InsufficientBalance);
+
+    let ann = amplification as u128 * N_COINS;
+
+    // y² + (b - D)·y = c, with c = D^(n+1) / (n^n·x·Ann) and b = x + D / Ann
+    let c = mul_div(mul_div(d, d, balance_x * N_COINS)?, d, ann * N_COINS)?;
+    let b = balance_x + d / ann;
+
+    // The quadratic is convex, so the first step from D lands above the
+    // root even when D starts below it, and later steps only decrease
+    let mut y = d;
+    for iteration in 0..MAX_ITERATIONS {
+        let previous = y;
+
+        // y = (y² + c) / (2·y + b - D), split so an overshooting first
+        // step cannot overflow y²
+        let denominator = checked(y.checked_mul(2), Some(b))?
+            .checked_sub(d)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        let (square, square_remainder) =
+            mul_div_rem(y, y, denominator).ok_or(DarkFlowError::MathOverflow)?;
+        let carry = u128::from(square_remainder >= denominator - c % denominator);
+        y = checked(square.checked_add(c / denominator), Some(carry))?;
+
+        if iteration > 0 && converged(y, previous) {
+            return Ok(y.max(previous));
+        }
+    }
+
+    err!(DarkFlowError::InvariantNotConverged)
+}
+
+/// Output for adding `input` (net of fees) to `reserve_in`
+///
+/// Rounded down by one unit against the trader, as in Curve.
+pub fn swap_output(input: u64, reserve_in: u64, reserve_out: u64, amplification: u64) -> Result<u64> {
+    let d = compute_d(reserve_in, reserve_out, amplification)?;
+    let y = compute_y(reserve_in as u128 + input as u128, d, amplification)?;
+    let output = (reserve_out as u128).saturating_sub(y).saturating_sub(1);
+
+    u64::try_from(output).map_err(|_| error!(DarkFlowError::MathOverflow))
+}
+
+/// Marginal price of the input token in the output token, times `scale`
+///
+/// The slope of the invariant: `y·(Ann·x + D_P) / (x·(Ann·y + D_P))`,
+/// which tends to `y / x` as the amplification goes to zero.
+pub fn spot_price(reserve_in: u64, reserve_out: u64, amplification: u64, scale: u128) -> Result<u128> {
+    let d = compute_d(reserve_in, reserve_out, amplification)?;
+    let (x, y) = (reserve_in as u128, reserve_out as u128);
+    let ann = amplification as u128 * N_COINS;
+    let d_p = d_product(d, x, y)?;
+
+    let numerator = checked(ann.checked_mul(x), Some(d_p))?;
+    let denominator = checked(ann.checked_mul(y), Some(d_p))?;
+    mul_div(mul_div(y, scale, x)?, numerator, denominator)
+}
+
+/// Whether a Newton step has reached the root
+///
+/// Started above the root, exact steps only ever decrease, so a step that
+/// does not is rounding noise. Callers keep the larger of the last two
+/// values, which rounds against the trader.
+fn converged(value: u128, previous: u128) -> bool {
+    value + 1 >= previous
+}
+
+/// `D_P = D^(n+1) / (n^n·x·y)`
+fn d_product(d: u128, x: u128, y: u128) -> Result<u128> {
+    mul_div(mul_div(d, d, x * N_COINS)?, d, y * N_COINS)
+}
+
+/// Sum of two checked products
+fn checked(a: Option<u128>, b: Option<u128>) -> Result<u128> {
+    a.zip(b)
+        .and_then(|(a, b)| a.checked_add(b))
+        .ok_or(DarkFlowError::MathOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALE: u128 = 1 << 64;
+
+    /// Reserve pairs from dust against huge balances up to near `u64::MAX`
+    const EXTREME_RESERVES: [(u64, u64); 6] = [
+        (1, 1_000_000_000_000_000_000),
+        (1_000_000_000_000_000_000, 1),
+        (1_000_000_000_000_000_000, 1_000_000_000_000_000_000),
+        (u64::MAX / 4, u64::MAX / 4),
+        (u64::MAX / 2, 1_000),
+        (1_000_000, 1_000_000),
+    ];
+
+    /// Left and right sides of the invariant for `n = 2`, with `Ann = A·n`
+    /// as the Newton steps use it
+    fn invariant_sides(x: u128, y: u128, d: u128, amplification: u64) -> (f64, f64) {
+        let (x, y, d) = (x as f64, y as f64, d as f64);
+        let ann = amplification as f64 * 2.0;
+        (ann * (x + y) + d, ann * d + d * d * d / (4.0 * x * y))
+    }
+
+    #[test]
+    fn balanced_invariant_is_the_sum() {
+        for amplification in [1, 100, MAX_AMPLIFICATION] {
+            let d = compute_d(1_000_000_000, 1_000_000_000, amplification).unwrap();
+            assert!(d.abs_diff(2_000_000_000) <= 1, "A={amplification}: D={d}");
+        }
+        assert_eq!(compute_d(0, 0, 100).unwrap(), 0);
+        assert!(compute_d(0, 1_000, 100).is_err());
+    }
+
+    #[test]
+    fn newton_converges_on_imbalanced_balances() {
+        for (x, y) in [(1_000_000_000u64, 3_000_000_000u64), (10, 1_000_000_000_000), (999_999_000, 1_000_000_000)] {
+            for amplification in [1, 100, MAX_AMPLIFICATION] {
+                let d = compute_d(x, y, amplification).unwrap();
+                let (left, right) = invariant_sides(x as u128, y as u128, d, amplification);
+                assert!(
+                    (left - right).abs() / left < 1e-9,
+                    "({x}, {y}) A={amplification}: {left} != {right}"
+                );
+
+                // Solving back for y lands on it, up to the rounding of D,
+                // which far out of balance moves y by many units
+                let solved = compute_y(x as u128, d, amplification).unwrap();
+                assert!(
+                    solved.abs_diff(y as u128) <= 2 + y as u128 / 1_000_000_000,
+                    "({x}, {y}) A={amplification}: y={solved}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn swap_output_near_parity_when_balanced() {
+        assert_eq!(swap_output(1_000_000, 1_000_000_000, 1_000_000_000, 100).unwrap(), 999_989);
+
+        // A larger amplification flattens the curve, so slippage shrinks
+        let low = swap_output(10_000_000, 1_000_000_000, 1_000_000_000, 1).unwrap();
+        let high = swap_output(10_000_000, 1_000_000_000, 1_000_000_000, 1_000).unwrap();
+        assert!(low < high && high < 10_000_000, "{low} {high}");
+    }
+
+    #[test]
+    fn spot_price_on_imbalanced_pool() {
+        let (x, y) = (1_000_000_000u64, 3_000_000_000u64);
+        let price = spot_price(x, y, 100, SCALE).unwrap();
+        assert!(price > SCALE && price < 3 * SCALE, "{price}");
+
+        // Matches the marginal rate of a small trade
+        let input = 1_000u64;
+        let marginal = swap_output(input, x, y, 100).unwrap() as f64 / input as f64;
+        let quoted = price as f64 / SCALE as f64;
+        assert!((marginal - quoted).abs() / quoted < 1e-2, "{marginal} {quoted}");
+
+        // Quoting the other way round gives the reciprocal
+        let inverse = spot_price(y, x, 100, SCALE).unwrap();
+        let product = quoted * (inverse as f64 / SCALE as f64);
+        assert!((product - 1.0).abs() < 1e-9, "{product}");
+
+        // With little amplification it tends to the constant product's y / x
+        let flat = spot_price(x, y, 1, SCALE).unwrap() as f64 / SCALE as f64;
+        assert!(flat > quoted && flat < 3.0, "{flat}");
+    }
+
+    #[test]
+    fn extreme_reserves_do_not_overflow() {
+        for (x, y) in EXTREME_RESERVES {
+            for amplification in [1, 100, MAX_AMPLIFICATION] {
+                assert!(compute_d(x, y, amplification).is_ok(), "D ({x}, {y}) A={amplification}");
+                assert!(
+                    swap_output(x / 10 + 1, x, y, amplification).unwrap() < y,
+                    "swap ({x}, {y}) A={amplification}"
+                );
+                assert!(spot_price(x, y, amplification, SCALE).is_ok(), "spot ({x}, {y}) A={amplification}");
+            }
+        }
+    }
+}
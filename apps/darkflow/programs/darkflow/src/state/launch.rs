@@ -1,4 +1,104 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use solana_zk_token_sdk::zk_token_elgamal::{ops, pod};
+use crate::errors::DarkFlowError;
+
+/// Size of a twisted ElGamal ciphertext (Pedersen commitment + decrypt handle)
+pub const ELGAMAL_CIPHERTEXT_LEN: usize = 64;
+
+/// Size of an encrypted purchase amount: low and high 32-bit limbs,
+/// each an ElGamal ciphertext under a key of the buyer's choosing
+pub const ENCRYPTED_AMOUNT_LEN: usize = 2 * ELGAMAL_CIPHERTEXT_LEN;
+
+/// Maximum number of price steps a stepped or exponential curve may span
+pub const MAX_CURVE_STEPS: u64 = 128;
+
+/// Bonding curve shape
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum CurveType {
+    /// price = initial_price + sold * param_a / param_b
+    Linear,
+    /// price grows by param_a bps every param_b tokens (geometric steps)
+    Exponential,
+    /// pump-style virtual reserves: param_a payment, param_b tokens
+    ConstantProduct,
+    /// price grows by param_a every param_b tokens (arithmetic steps)
+    Stepped,
+}
+
+/// Bonding curve parameters evaluated on-chain
+///
+/// Stored and evaluated in the clear: every buyer can price a purchase
+/// before making it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct CurveParams {
+    /// Curve shape
+    pub curve_type: CurveType,
+
+    /// Linear: slope numerator; Exponential: growth per step (bps);
+    /// ConstantProduct: virtual payment reserve; Stepped: price increment per step
+    pub param_a: u64,
+
+    /// Linear: slope denominator; Exponential / Stepped: tokens per step;
+    /// ConstantProduct: virtual token reserve
+    pub param_b: u64,
+}
+
+impl CurveParams {
+    /// Validate the parameters against the launch supply
+    pub fn validate(&self, max_supply: u64) -> Result<()> {
+        match self.curve_type {
+            CurveType::Linear => {
+                require!(self.param_b > 0, DarkFlowError::InvalidCurveParams);
+            }
+            CurveType::Exponential | CurveType::Stepped => {
+                require!(self.param_b > 0, DarkFlowError::InvalidCurveParams);
+                require!(
+                    max_supply.div_ceil(self.param_b) <= MAX_CURVE_STEPS,
+                    DarkFlowError::InvalidCurveParams
+                );
+            }
+            CurveType::ConstantProduct => {
+                require!(self.param_a > 0, DarkFlowError::InvalidCurveParams);
+                require!(self.param_b > max_supply, DarkFlowError::InvalidCurveParams);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Raise limits of a launch, in payment token units (0 = no limit)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub struct LaunchCaps {
+    /// Minimum raise; below it the sale fails and every buyer is refunded
+    pub soft_cap: u64,
+
+    /// Maximum raise; the launch sells out once it is reached
+    pub hard_cap: u64,
+
+    /// Maximum total payment per wallet
+    pub max_per_wallet: u64,
+}
+
+impl LaunchCaps {
+    /// Largest payment a wallet that has paid `paid` may still spend
+    pub fn remaining_allowance(&self, paid: u64) -> u64 {
+        if self.max_per_wallet == 0 {
+            u64::MAX
+        } else {
+            self.max_per_wallet.saturating_sub(paid)
+        }
+    }
+
+    /// Validate that the caps are consistent
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.hard_cap == 0 || self.hard_cap >= self.soft_cap,
+            DarkFlowError::InvalidLaunchCaps
+        );
+        Ok(())
+    }
+}
 
 /// Confidential token launch status
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
@@ -13,10 +113,12 @@ pub enum LaunchStatus {
     Ended,
 }
 
-/// Confidential token launch with private bonding curve
+/// Confidential token launch
 ///
-/// Buyers cannot see how much others have purchased,
-/// preventing front-running of token launches.
+/// Payments are public token transfers priced on a public curve, so the
+/// raise and the supply sold are public. What stays out
+/// of account state is how much each buyer holds (see
+/// `ConfidentialPurchase`).
 #[account]
 #[derive(InitSpace)]
 pub struct ConfidentialLaunch {
@@ -29,10 +131,8 @@ pub struct ConfidentialLaunch {
     /// Payment token (e.g., SOL, USDC)
     pub payment_mint: Pubkey,
 
-    /// Encrypted bonding curve parameters
-    /// Contains: curve_type, a, b, c coefficients
-    #[max_len(256)]
-    pub encrypted_curve_params: Vec<u8>,
+    /// Bonding curve evaluated on every purchase (public)
+    pub curve: CurveParams,
 
     /// Initial price per token (in payment token)
     pub initial_price: u64,
@@ -40,20 +140,19 @@ pub struct ConfidentialLaunch {
     /// Maximum supply available for sale
     pub max_supply: u64,
 
-    /// Current supply sold (encrypted aggregate)
-    /// Only the creator can decrypt the exact amount
-    #[max_len(64)]
-    pub encrypted_sold: Vec<u8>,
-
-    /// Public sold count (rounded/approximate for UI)
-    pub approximate_sold: u64,
-
     /// Number of unique buyers (public)
     pub buyer_count: u64,
 
     /// Total payment received (public aggregate)
+    ///
+    /// Purchases are charged so this is always the exact curve cost of the
+    /// supply sold, which is derived from it rather than stored. Anyone
+    /// can derive it the same way.
     pub total_payments: u64,
 
+    /// Soft cap, hard cap and per-wallet limit
+    pub caps: LaunchCaps,
+
     /// Launch status
     pub status: LaunchStatus,
 
@@ -69,8 +168,17 @@ pub struct ConfidentialLaunch {
     /// Creation timestamp
     pub created_at: i64,
 
-    /// Bump seed
+    /// Bump seed of the launch PDA (`[b"launch", creator, token_mint]`)
     pub bump: u8,
+
+    /// Bump seed of the program-owned vault holding the tokens for sale
+    pub token_vault_bump: u8,
+
+    /// Bump seed of the program-owned vault collecting payments
+    pub payment_vault_bump: u8,
+
+    /// Whether the creator has withdrawn the proceeds and unsold tokens
+    pub proceeds_withdrawn: bool,
 }
 
 impl ConfidentialLaunch {
@@ -95,17 +203,50 @@ impl ConfidentialLaunch {
         Ok(true)
     }
 
+    /// Check whether the sale is over (sold out, ended or past its end time)
+    pub fn is_sale_over(&self) -> Result<bool> {
+        match self.status {
+            LaunchStatus::SoldOut | LaunchStatus::Ended => Ok(true),
+            LaunchStatus::Cancelled => Ok(false),
+            LaunchStatus::Active => {
+                let now = Clock::get()?.unix_timestamp;
+                Ok(self.end_time > 0 && now > self.end_time)
+            }
+        }
+    }
+
+    /// Check whether the public raise reached the soft cap
+    pub fn soft_cap_met(&self) -> bool {
+        self.total_payments >= self.caps.soft_cap
+    }
+
+    /// Check whether buyers can be refunded: cancelled, or over under the soft cap
+    pub fn is_refundable(&self) -> Result<bool> {
+        if self.status == LaunchStatus::Cancelled {
+            return Ok(true);
+        }
+        Ok(self.is_sale_over()? && !self.soft_cap_met())
+    }
+
+    /// Largest payment the next purchase may spend under the hard cap
+    pub fn remaining_raise(&self) -> u64 {
+        if self.caps.hard_cap == 0 {
+            u64::MAX
+        } else {
+            self.caps.hard_cap.saturating_sub(self.total_payments)
+        }
+    }
+
     /// Record a purchase
-    pub fn record_purchase(&mut self, payment_amount: u64, encrypted_sold_update: Vec<u8>) {
-        self.total_payments = self.total_payments.saturating_add(payment_amount);
-        self.buyer_count = self.buyer_count.saturating_add(1);
-        self.encrypted_sold = encrypted_sold_update;
-
-        // Update approximate sold (for UI display)
-        // This is intentionally imprecise to protect buyer privacy
-        self.approximate_sold = self.approximate_sold.saturating_add(
-            payment_amount / self.initial_price
-        );
+    pub fn record_purchase(&mut self, payment_amount: u64, new_buyer: bool) -> Result<()> {
+        self.total_payments = self
+            .total_payments
+            .checked_add(payment_amount)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        if new_buyer {
+            self.buyer_count = self.buyer_count.saturating_add(1);
+        }
+        Ok(())
     }
 
     /// End the launch
@@ -124,7 +265,79 @@ impl ConfidentialLaunch {
     }
 }
 
-/// Encrypted purchase record
+/// Add two `ciphertext(lo) || ciphertext(hi)` encrypted amounts
+fn add_encrypted_amounts(
+    left: &[u8; ENCRYPTED_AMOUNT_LEN],
+    right: &[u8],
+) -> Result<[u8; ENCRYPTED_AMOUNT_LEN]> {
+    require!(
+        right.len() == ENCRYPTED_AMOUNT_LEN,
+        DarkFlowError::InvalidEncryptedData
+    );
+    let (left_lo, left_hi) = left.split_at(ELGAMAL_CIPHERTEXT_LEN);
+    let (right_lo, right_hi) = right.split_at(ELGAMAL_CIPHERTEXT_LEN);
+
+    let mut sum = [0u8; ENCRYPTED_AMOUNT_LEN];
+    sum[..ELGAMAL_CIPHERTEXT_LEN].copy_from_slice(&add_ciphertexts(left_lo, right_lo)?);
+    sum[ELGAMAL_CIPHERTEXT_LEN..].copy_from_slice(&add_ciphertexts(left_hi, right_hi)?);
+
+    Ok(sum)
+}
+
+/// Add two serialized ElGamal ciphertexts
+fn add_ciphertexts(left: &[u8], right: &[u8]) -> Result<[u8; ELGAMAL_CIPHERTEXT_LEN]> {
+    let left: [u8; ELGAMAL_CIPHERTEXT_LEN] = left
+        .try_into()
+        .map_err(|_| DarkFlowError::InvalidEncryptedData)?;
+    let right: [u8; ELGAMAL_CIPHERTEXT_LEN] = right
+        .try_into()
+        .map_err(|_| DarkFlowError::InvalidEncryptedData)?;
+
+    let sum = ops::add(&pod::ElGamalCiphertext(left), &pod::ElGamalCiphertext(right))
+        .ok_or(DarkFlowError::InvalidEncryptedData)?;
+
+    Ok(sum.0)
+}
+
+/// A buyer's running purchase totals, revealed to spend or update them
+///
+/// Must open `ConfidentialPurchase::commitment`:
+/// SHA-256("darkflow_purchase" || purchase || payment_amount (u64 LE)
+///   || token_amount (u64 LE) || salt (32 bytes))
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PurchaseReveal {
+    /// Total paid by the buyer
+    pub payment_amount: u64,
+
+    /// Total tokens owed to the buyer
+    pub token_amount: u64,
+
+    /// Blinding salt
+    pub salt: [u8; 32],
+}
+
+impl PurchaseReveal {
+    /// Commitment these totals open for the purchase account `purchase`
+    pub fn commitment(&self, purchase: &Pubkey) -> [u8; 32] {
+        hashv(&[
+            b"darkflow_purchase",
+            purchase.as_ref(),
+            &self.payment_amount.to_le_bytes(),
+            &self.token_amount.to_le_bytes(),
+            &self.salt,
+        ])
+        .to_bytes()
+    }
+}
+
+/// Encrypted purchase record, one per buyer per launch
+///
+/// Repeat purchases by the same wallet accumulate into this account. The
+/// totals are held only behind `commitment`; the buyer reveals them to buy
+/// again, claim or refund, and the program pays out the revealed values.
+/// Reveals travel in instruction data, so this keeps per-buyer amounts out
+/// of account state that anyone can scan, not out of the buyer's own
+/// transactions.
 #[account]
 #[derive(InitSpace)]
 pub struct ConfidentialPurchase {
@@ -134,17 +347,15 @@ pub struct ConfidentialPurchase {
     /// Launch this purchase belongs to
     pub launch: Pubkey,
 
-    /// Encrypted purchase amount
-    #[max_len(128)]
-    pub encrypted_amount: Vec<u8>,
+    /// Encrypted purchase amount (sum of all purchases, as lo/hi ElGamal
+    /// ciphertexts under the buyer's key), for the buyer's own records
+    pub encrypted_amount: [u8; ENCRYPTED_AMOUNT_LEN],
 
-    /// Commitment to purchase
+    /// Commitment to the buyer's running totals (see `PurchaseReveal`),
+    /// zero before the first purchase
     pub commitment: [u8; 32],
 
-    /// Payment amount (public)
-    pub payment_amount: u64,
-
-    /// Purchase timestamp
+    /// Purchase timestamp (latest purchase)
     pub purchased_at: i64,
 
     /// Whether tokens have been claimed
@@ -153,3 +364,56 @@ pub struct ConfidentialPurchase {
     /// Bump seed
     pub bump: u8,
 }
+
+impl ConfidentialPurchase {
+    /// Whether this account was just created by the current purchase
+    pub fn is_new(&self) -> bool {
+        self.buyer == Pubkey::default()
+    }
+
+    /// Check that `reveal` opens the stored totals
+    ///
+    /// A record with no purchases yet opens only to zero totals.
+    pub fn verify_reveal(&self, purchase: &Pubkey, reveal: &PurchaseReveal) -> Result<()> {
+        if self.commitment == [0u8; 32] {
+            require!(
+                reveal.payment_amount == 0 && reveal.token_amount == 0,
+                DarkFlowError::CommitmentMismatch
+            );
+        } else {
+            require!(
+                reveal.commitment(purchase) == self.commitment,
+                DarkFlowError::CommitmentMismatch
+            );
+        }
+        Ok(())
+    }
+
+    /// Add a purchase to the revealed totals and commit to them under `salt`
+    pub fn record(
+        &mut self,
+        purchase: &Pubkey,
+        encrypted_amount: &[u8],
+        totals: &PurchaseReveal,
+        salt: [u8; 32],
+        payment_amount: u64,
+        tokens: u64,
+    ) -> Result<()> {
+        self.encrypted_amount = add_encrypted_amounts(&self.encrypted_amount, encrypted_amount)?;
+
+        let updated = PurchaseReveal {
+            payment_amount: totals
+                .payment_amount
+                .checked_add(payment_amount)
+                .ok_or(DarkFlowError::MathOverflow)?,
+            token_amount: totals
+                .token_amount
+                .checked_add(tokens)
+                .ok_or(DarkFlowError::MathOverflow)?,
+            salt,
+        };
+        self.commitment = updated.commitment(purchase);
+        self.purchased_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+}
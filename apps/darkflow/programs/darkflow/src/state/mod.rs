@@ -1,9 +1,19 @@
 pub mod pool;
 pub mod position;
+pub mod position_tree;
 pub mod order;
 pub mod launch;
+pub mod verifying_key;
+pub mod solver;
+pub mod oracle;
+pub mod tick;
 
 pub use pool::*;
 pub use position::*;
+pub use position_tree::*;
 pub use order::*;
 pub use launch::*;
+pub use verifying_key::*;
+pub use solver::*;
+pub use oracle::*;
+pub use tick::*;
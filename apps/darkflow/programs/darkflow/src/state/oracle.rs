@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+use crate::{CUMULATIVE_PRICE_SCALE, PRICE_SCALE};
+
+/// Number of price observations kept per pool
+pub const MAX_OBSERVATIONS: usize = 128;
+
+/// Minimum spacing between recorded observations
+pub const OBSERVATION_PERIOD_SECONDS: i64 = 60;
+
+/// Snapshot of a pool's cumulative prices
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct PriceObservation {
+    /// When the snapshot was taken
+    pub timestamp: i64,
+
+    /// Cumulative price of token A in token B (Q64.64 · seconds, wrapping)
+    pub price_a_cumulative: u128,
+
+    /// Cumulative price of token B in token A (Q64.64 · seconds, wrapping)
+    pub price_b_cumulative: u128,
+}
+
+/// Time-weighted average price over a window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TwapQuote {
+    /// Average price of token A in token B, scaled by PRICE_SCALE
+    pub price_a: u128,
+
+    /// Average price of token B in token A, scaled by PRICE_SCALE
+    pub price_b: u128,
+
+    /// Seconds actually averaged over (at least the requested window)
+    pub window: i64,
+}
+
+/// Ring buffer of cumulative price observations for a pool
+///
+/// Trades record an observation at most once per
+/// `OBSERVATION_PERIOD_SECONDS`, so the buffer covers a bit over two hours
+/// of history. Other programs read TWAPs from it via `consult_twap`.
+#[account]
+#[derive(InitSpace)]
+pub struct PriceOracle {
+    /// Pool this oracle belongs to
+    pub pool: Pubkey,
+
+    /// Recorded observations, used as a ring once full
+    #[max_len(MAX_OBSERVATIONS)]
+    pub observations: Vec<PriceObservation>,
+
+    /// Index of the latest observation
+    pub observation_index: u16,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl PriceOracle {
+    /// Latest observation, if any
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        self.observations.get(self.observation_index as usize)
+    }
+
+    /// Record an observation unless the latest one is too recent
+    pub fn record(&mut self, observation: PriceObservation) {
+        if let Some(latest) = self.latest() {
+            if observation.timestamp - latest.timestamp < OBSERVATION_PERIOD_SECONDS {
+                return;
+            }
+        }
+
+        if self.observations.len() < MAX_OBSERVATIONS {
+            self.observation_index = self.observations.len() as u16;
+            self.observations.push(observation);
+        } else {
+            self.observation_index = (self.observation_index + 1) % MAX_OBSERVATIONS as u16;
+            self.observations[self.observation_index as usize] = observation;
+        }
+    }
+
+    /// Newest observation taken at or before `timestamp`
+    pub fn observation_before(&self, timestamp: i64) -> Option<&PriceObservation> {
+        let count = self.observations.len();
+        (0..count)
+            .map(|age| &self.observations[(self.observation_index as usize + count - age) % count])
+            .find(|observation| observation.timestamp <= timestamp)
+    }
+
+    /// Average prices between `start` and the current cumulative prices
+    pub fn twap(start: &PriceObservation, current: &PriceObservation) -> Result<TwapQuote> {
+        let elapsed = current.timestamp - start.timestamp;
+        require!(elapsed > 0, DarkFlowError::TwapWindowUnavailable);
+
+        let average = |end: u128, begin: u128| -> Result<u128> {
+            q64_to_price(end.wrapping_sub(begin) / elapsed as u128)
+        };
+
+        Ok(TwapQuote {
+            price_a: average(current.price_a_cumulative, start.price_a_cumulative)?,
+            price_b: average(current.price_b_cumulative, start.price_b_cumulative)?,
+            window: elapsed,
+        })
+    }
+}
+
+/// Convert a Q64.64 price to PRICE_SCALE fixed point
+fn q64_to_price(value: u128) -> Result<u128> {
+    let whole = (value / CUMULATIVE_PRICE_SCALE)
+        .checked_mul(PRICE_SCALE)
+        .ok_or(DarkFlowError::MathOverflow)?;
+    let fraction = (value % CUMULATIVE_PRICE_SCALE) * PRICE_SCALE / CUMULATIVE_PRICE_SCALE;
+    whole.checked_add(fraction).ok_or(DarkFlowError::MathOverflow.into())
+}
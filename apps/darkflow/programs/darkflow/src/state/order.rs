@@ -1,4 +1,12 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use crate::errors::DarkFlowError;
+
+/// Lamports paid from the order account's rent to whoever expires it
+pub const EXPIRY_CRANK_REWARD_LAMPORTS: u64 = 10_000;
+
+/// Maximum number of dark orders settled in one batch
+pub const MAX_BATCH_ORDERS: usize = 8;
 
 /// Dark order status
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
@@ -13,6 +21,53 @@ pub enum OrderStatus {
     Expired,
 }
 
+/// Swap direction relative to the pool's token A and token B mints
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum SwapDirection {
+    /// Sell token A for token B
+    AToB,
+    /// Sell token B for token A
+    BToA,
+}
+
+impl SwapDirection {
+    /// Input and output mints for this direction
+    pub fn mints(&self, token_a_mint: Pubkey, token_b_mint: Pubkey) -> (Pubkey, Pubkey) {
+        match self {
+            SwapDirection::AToB => (token_a_mint, token_b_mint),
+            SwapDirection::BToA => (token_b_mint, token_a_mint),
+        }
+    }
+}
+
+/// Order parameters revealed by the solver at execution
+///
+/// Must open `DarkOrder::commitment`:
+/// SHA-256(min_output (u64 LE) || slippage_bps (u16 LE) || salt (32 bytes))
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct OrderReveal {
+    /// Minimum output the maker accepts
+    pub min_output: u64,
+
+    /// Slippage tolerance the maker chose (basis points)
+    pub slippage_bps: u16,
+
+    /// Blinding salt
+    pub salt: [u8; 32],
+}
+
+impl OrderReveal {
+    /// Commitment these parameters open
+    pub fn commitment(&self) -> [u8; 32] {
+        hashv(&[
+            &self.min_output.to_le_bytes(),
+            &self.slippage_bps.to_le_bytes(),
+            &self.salt,
+        ])
+        .to_bytes()
+    }
+}
+
 /// Dark order for private swap execution
 ///
 /// Order parameters (amount, min output) are encrypted.
@@ -32,12 +87,16 @@ pub struct DarkOrder {
     /// Output token mint
     pub output_mint: Pubkey,
 
+    /// Swap direction relative to the pool mints
+    pub direction: SwapDirection,
+
     /// Encrypted order parameters
-    /// Contains: input_amount, min_output_amount, slippage_bps
+    /// Contains: input_amount, min_output_amount, slippage_bps, salt
     #[max_len(256)]
     pub encrypted_params: Vec<u8>,
 
     /// Commitment to order parameters (for verification)
+    /// SHA-256(min_output_le || slippage_bps_le || salt), see `OrderReveal`
     pub commitment: [u8; 32],
 
     /// Input amount (public, for escrow)
@@ -68,9 +127,11 @@ pub struct DarkOrder {
 
 impl DarkOrder {
     /// Create a new dark order
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         maker: Pubkey,
         pool: Pubkey,
+        direction: SwapDirection,
         input_mint: Pubkey,
         output_mint: Pubkey,
         encrypted_params: Vec<u8>,
@@ -84,6 +145,7 @@ impl DarkOrder {
             pool,
             input_mint,
             output_mint,
+            direction,
             encrypted_params,
             commitment,
             input_amount,
@@ -108,6 +170,15 @@ impl DarkOrder {
         Ok(self.status == OrderStatus::Pending && !self.is_expired()?)
     }
 
+    /// Check revealed parameters against the order commitment
+    pub fn verify_reveal(&self, reveal: &OrderReveal) -> Result<()> {
+        require!(
+            reveal.commitment() == self.commitment,
+            DarkFlowError::CommitmentMismatch
+        );
+        Ok(())
+    }
+
     /// Mark order as filled
     pub fn fill(&mut self, solver: Pubkey, encrypted_output: Vec<u8>) -> Result<()> {
         self.status = OrderStatus::Filled;
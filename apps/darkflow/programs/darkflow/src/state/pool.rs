@@ -1,4 +1,35 @@
 use anchor_lang::prelude::*;
+use crate::{CircuitKind, PriceObservation};
+use crate::errors::DarkFlowError;
+use crate::{stable_math, tick_math};
+
+/// LP shares permanently locked by the first deposit, so the share price
+/// can never be inflated from an empty pool
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+/// Fixed-point scale of the fee growth accumulators (Q64.64)
+pub const FEE_GROWTH_SCALE: u128 = 1 << 64;
+
+/// Fixed-point scale of quoted prices (token B per token A)
+pub const PRICE_SCALE: u128 = 1_000_000_000_000;
+
+/// Fixed-point scale of the cumulative price accumulators (Q64.64)
+pub const CUMULATIVE_PRICE_SCALE: u128 = 1 << 64;
+
+/// Length of one rolling volume bucket
+pub const VOLUME_BUCKET_SECONDS: i64 = 3_600;
+
+/// Number of rolling volume buckets (24 hours)
+pub const VOLUME_BUCKETS: usize = 24;
+
+/// Invariant a full-range pool prices swaps on
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum SwapCurve {
+    /// x * y = k
+    ConstantProduct,
+    /// Curve-style StableSwap, for tokens that trade near one to one
+    StableSwap,
+}
 
 /// Dark liquidity pool with encrypted positions
 ///
@@ -10,6 +41,10 @@ pub struct DarkPool {
     /// Pool authority
     pub authority: Pubkey,
 
+    /// Authority proposed by the current one, pending acceptance
+    /// (default = none)
+    pub pending_authority: Pubkey,
+
     /// Token A mint
     pub token_a_mint: Pubkey,
 
@@ -23,9 +58,66 @@ pub struct DarkPool {
     /// Fee rate in basis points (e.g., 30 = 0.3%)
     pub fee_rate_bps: u16,
 
+    /// Share of each swap fee paid to the treasury, in basis points of the fee
+    pub protocol_fee_bps: u16,
+
+    /// Owner of the token accounts that receive protocol fees
+    pub treasury: Pubkey,
+
     /// Total positions (public aggregate)
     pub position_count: u64,
 
+    /// Total LP shares outstanding (public aggregate)
+    pub total_shares: u64,
+
+    /// Cumulative LP fees earned per share in token A (scaled by FEE_GROWTH_SCALE),
+    /// or per unit of liquidity in concentrated mode
+    pub fee_growth_a: u128,
+
+    /// Cumulative LP fees earned per share in token B (scaled by FEE_GROWTH_SCALE),
+    /// or per unit of liquidity in concentrated mode
+    pub fee_growth_b: u128,
+
+    /// LP fees in token A accrued in the vault but not yet claimed
+    pub lp_fees_owed_a: u64,
+
+    /// LP fees in token B accrued in the vault but not yet claimed
+    pub lp_fees_owed_b: u64,
+
+    /// Protocol fees in token A accrued in the vault but not yet collected
+    pub protocol_fees_owed_a: u64,
+
+    /// Protocol fees in token B accrued in the vault but not yet collected
+    pub protocol_fees_owed_b: u64,
+
+    /// Tick spacing of concentrated liquidity (0 = full-range constant product)
+    pub tick_spacing: u16,
+
+    /// Square root of the price of token A in token B (Q64.64, concentrated mode)
+    pub sqrt_price: u128,
+
+    /// Tick containing the current price (concentrated mode)
+    pub tick_current: i32,
+
+    /// Liquidity of the positions whose range contains the current price
+    /// (concentrated mode)
+    pub liquidity: u128,
+
+    /// Swap invariant of full-range liquidity
+    pub curve: SwapCurve,
+
+    /// StableSwap amplification at the start of the current ramp
+    pub initial_amplification: u64,
+
+    /// StableSwap amplification at the end of the current ramp
+    pub future_amplification: u64,
+
+    /// Start of the current amplification ramp
+    pub initial_amplification_time: i64,
+
+    /// End of the current amplification ramp
+    pub future_amplification_time: i64,
+
     /// Total orders processed
     pub order_count: u64,
 
@@ -35,7 +127,31 @@ pub struct DarkPool {
     /// Total volume in token B (public aggregate)
     pub total_volume_b: u64,
 
-    /// Pool state commitment (Merkle root of all positions)
+    /// Hourly token A volume, indexed by hour modulo VOLUME_BUCKETS
+    pub volume_buckets_a: [u64; VOLUME_BUCKETS],
+
+    /// Hourly token B volume, indexed by hour modulo VOLUME_BUCKETS
+    pub volume_buckets_b: [u64; VOLUME_BUCKETS],
+
+    /// Hour (unix time / VOLUME_BUCKET_SECONDS) of the latest volume bucket
+    pub volume_hour: i64,
+
+    /// Time-integrated price of token A in token B (Q64.64 · seconds, wrapping)
+    pub price_a_cumulative: u128,
+
+    /// Time-integrated price of token B in token A (Q64.64 · seconds, wrapping)
+    pub price_b_cumulative: u128,
+
+    /// Timestamp the cumulative prices were last advanced to
+    pub price_timestamp_last: i64,
+
+    /// Token A reserve left by the latest trade or liquidity change
+    pub reserve_a_last: u64,
+
+    /// Token B reserve left by the latest trade or liquidity change
+    pub reserve_b_last: u64,
+
+    /// Pool state commitment (root of the position tree)
     pub state_commitment: [u8; 32],
 
     /// Timestamp of last state update
@@ -44,6 +160,18 @@ pub struct DarkPool {
     /// Whether the pool is active
     pub is_active: bool,
 
+    /// Emergency mode: trading and deposits stop, LPs can still withdraw
+    pub emergency_mode: bool,
+
+    /// Swap circuit verifying key version accepted by this pool (0 = none)
+    pub swap_vk_version: u32,
+
+    /// Execution circuit verifying key version accepted by this pool (0 = none)
+    pub execution_vk_version: u32,
+
+    /// Position circuit verifying key version accepted by this pool (0 = none)
+    pub position_vk_version: u32,
+
     /// Bump seed for PDA
     pub bump: u8,
 }
@@ -60,10 +188,16 @@ pub struct PoolAggregates {
     /// Number of liquidity providers
     pub lp_count: u64,
 
-    /// 24-hour volume
-    pub volume_24h: u64,
+    /// Rolling 24-hour volume in token A
+    pub volume_24h_a: u64,
 
-    /// Current utilization rate (basis points)
+    /// Rolling 24-hour volume in token B
+    pub volume_24h_b: u64,
+
+    /// Spot price of token A in token B, scaled by PRICE_SCALE
+    pub spot_price: u128,
+
+    /// 24-hour volume as a share of TVL (basis points, capped at 100%)
     pub utilization_bps: u16,
 
     /// Average position size (encrypted, for display only)
@@ -93,9 +227,292 @@ impl DarkPool {
         self.order_count = self.order_count.saturating_add(1);
     }
 
-    /// Add to volume
-    pub fn add_volume(&mut self, amount_a: u64, amount_b: u64) {
+    /// Add to volume, including the current hour's rolling bucket
+    pub fn add_volume(&mut self, amount_a: u64, amount_b: u64) -> Result<()> {
         self.total_volume_a = self.total_volume_a.saturating_add(amount_a);
         self.total_volume_b = self.total_volume_b.saturating_add(amount_b);
+
+        let hour = Clock::get()?.unix_timestamp / VOLUME_BUCKET_SECONDS;
+        if hour > self.volume_hour {
+            // Clear the buckets of the hours skipped since the last trade
+            let stale = (hour - self.volume_hour).min(VOLUME_BUCKETS as i64);
+            for offset in 0..stale {
+                let index = bucket_index(hour - offset);
+                self.volume_buckets_a[index] = 0;
+                self.volume_buckets_b[index] = 0;
+            }
+            self.volume_hour = hour;
+        }
+
+        let index = bucket_index(self.volume_hour);
+        self.volume_buckets_a[index] = self.volume_buckets_a[index].saturating_add(amount_a);
+        self.volume_buckets_b[index] = self.volume_buckets_b[index].saturating_add(amount_b);
+        Ok(())
+    }
+
+    /// Advance the cumulative prices to `now` at the stored reserves
+    ///
+    /// Called at the start of every instruction that changes the reserves,
+    /// so every second is weighted by the price that held during it.
+    /// Tokens sent straight to a vault are only priced in once a trade or
+    /// liquidity change records the reserves it leaves.
+    pub fn accumulate_price(&mut self, now: i64) -> PriceObservation {
+        let observation = self.price_observation(now);
+        self.price_a_cumulative = observation.price_a_cumulative;
+        self.price_b_cumulative = observation.price_b_cumulative;
+        self.price_timestamp_last = observation.timestamp;
+        observation
+    }
+
+    /// Cumulative prices extrapolated to `now` at the stored reserves
+    pub fn price_observation(&self, now: i64) -> PriceObservation {
+        let mut observation = PriceObservation {
+            timestamp: self.price_timestamp_last.max(now),
+            price_a_cumulative: self.price_a_cumulative,
+            price_b_cumulative: self.price_b_cumulative,
+        };
+
+        let elapsed = now - self.price_timestamp_last;
+        if elapsed <= 0 {
+            return observation;
+        }
+        if let Some((price_a, price_b)) = self.q64_prices(self.reserve_a_last, self.reserve_b_last, now) {
+            observation.price_a_cumulative = observation
+                .price_a_cumulative
+                .wrapping_add(price_a.wrapping_mul(elapsed as u128));
+            observation.price_b_cumulative = observation
+                .price_b_cumulative
+                .wrapping_add(price_b.wrapping_mul(elapsed as u128));
+        }
+        observation
+    }
+
+    /// Spot prices of token A in token B and of token B in token A (Q64.64)
+    ///
+    /// Concentrated pools price off `sqrt_price`; full-range pools off the
+    /// slope of their curve at the reserves. Constant product prices fit
+    /// Q64.64 since the reserves are u64. None when the pool has no price
+    /// or it overflows.
+    pub fn q64_prices(&self, reserve_a: u64, reserve_b: u64, now: i64) -> Option<(u128, u128)> {
+        if self.is_concentrated() {
+            let price_a = tick_math::mul_div(self.sqrt_price, self.sqrt_price, CUMULATIVE_PRICE_SCALE).ok()?;
+            let price_b = tick_math::mul_div(CUMULATIVE_PRICE_SCALE, CUMULATIVE_PRICE_SCALE, price_a).ok()?;
+            Some((price_a, price_b))
+        } else if reserve_a == 0 || reserve_b == 0 {
+            None
+        } else if self.curve == SwapCurve::StableSwap {
+            let amplification = self.amplification(now);
+            Some((
+                stable_math::spot_price(reserve_a, reserve_b, amplification, CUMULATIVE_PRICE_SCALE).ok()?,
+                stable_math::spot_price(reserve_b, reserve_a, amplification, CUMULATIVE_PRICE_SCALE).ok()?,
+            ))
+        } else {
+            Some((
+                ((reserve_b as u128) << 64) / reserve_a as u128,
+                ((reserve_a as u128) << 64) / reserve_b as u128,
+            ))
+        }
+    }
+
+    /// StableSwap amplification at `now`, moving linearly along any ramp
+    pub fn amplification(&self, now: i64) -> u64 {
+        let (initial, future) = (self.initial_amplification, self.future_amplification);
+        if now >= self.future_amplification_time {
+            return future;
+        }
+        if now <= self.initial_amplification_time {
+            return initial;
+        }
+
+        let elapsed = (now - self.initial_amplification_time) as u128;
+        let duration = (self.future_amplification_time - self.initial_amplification_time) as u128;
+        if future >= initial {
+            initial + ((future - initial) as u128 * elapsed / duration) as u64
+        } else {
+            initial - ((initial - future) as u128 * elapsed / duration) as u64
+        }
+    }
+
+    /// Volume traded in the 24 hours up to `now`
+    pub fn volume_24h(&self, now: i64) -> (u64, u64) {
+        let hour = now / VOLUME_BUCKET_SECONDS;
+        let (mut volume_a, mut volume_b) = (0u64, 0u64);
+        for offset in 0..VOLUME_BUCKETS as i64 {
+            let bucket_hour = self.volume_hour - offset;
+            if hour - bucket_hour < VOLUME_BUCKETS as i64 {
+                let index = bucket_index(bucket_hour);
+                volume_a = volume_a.saturating_add(self.volume_buckets_a[index]);
+                volume_b = volume_b.saturating_add(self.volume_buckets_b[index]);
+            }
+        }
+        (volume_a, volume_b)
+    }
+
+    /// Reserves backing LP shares: the vault balances less accrued fees
+    pub fn reserves(&self, vault_a: u64, vault_b: u64) -> (u64, u64) {
+        (
+            vault_a
+                .saturating_sub(self.lp_fees_owed_a)
+                .saturating_sub(self.protocol_fees_owed_a),
+            vault_b
+                .saturating_sub(self.lp_fees_owed_b)
+                .saturating_sub(self.protocol_fees_owed_b),
+        )
+    }
+
+    /// Store the reserves the cumulative prices accrue at from here on,
+    /// given the vault balances an instruction leaves
+    pub fn record_reserves(&mut self, vault_a: u64, vault_b: u64) {
+        (self.reserve_a_last, self.reserve_b_last) = self.reserves(vault_a, vault_b);
+    }
+
+    /// Whether LPs can withdraw and claim fees
+    ///
+    /// Emergency mode keeps exits open even while the pool is paused.
+    pub fn accepts_withdrawals(&self) -> bool {
+        self.is_active || self.emergency_mode
+    }
+
+    /// Whether the pool runs in concentrated liquidity mode
+    pub fn is_concentrated(&self) -> bool {
+        self.tick_spacing != 0
+    }
+
+    /// Split the fee on a swap input between LPs and the treasury
+    ///
+    /// The fee stays in the input vault but is set aside from the reserves.
+    /// The LP portion is credited to every share through the fee growth
+    /// accumulator.
+    pub fn accrue_swap_fee(&mut self, input_amount: u64, input_is_a: bool) -> Result<()> {
+        let fee = (input_amount as u128 * self.fee_rate_bps as u128 / 10000) as u64;
+        self.credit_fee(fee, input_is_a, self.total_shares as u128)
+    }
+
+    /// Split a swap fee between LPs and the treasury, crediting the LP
+    /// portion to `liquidity` units (shares, or active concentrated liquidity)
+    pub fn credit_fee(&mut self, fee: u64, input_is_a: bool, liquidity: u128) -> Result<()> {
+        if fee == 0 || liquidity == 0 {
+            return Ok(());
+        }
+
+        let protocol_fee = if self.treasury == Pubkey::default() {
+            0
+        } else {
+            (fee as u128 * self.protocol_fee_bps as u128 / 10000) as u64
+        };
+        let lp_fee = fee - protocol_fee;
+        let growth = (lp_fee as u128 * FEE_GROWTH_SCALE) / liquidity;
+
+        let (fee_growth, lp_fees_owed, protocol_fees_owed) = if input_is_a {
+            (&mut self.fee_growth_a, &mut self.lp_fees_owed_a, &mut self.protocol_fees_owed_a)
+        } else {
+            (&mut self.fee_growth_b, &mut self.lp_fees_owed_b, &mut self.protocol_fees_owed_b)
+        };
+        *fee_growth = fee_growth.wrapping_add(growth);
+        *lp_fees_owed = lp_fees_owed
+            .checked_add(lp_fee)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        *protocol_fees_owed = protocol_fees_owed
+            .checked_add(protocol_fee)
+            .ok_or(DarkFlowError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Release claimed LP fees from the accrued totals
+    pub fn release_lp_fees(&mut self, amount_a: u64, amount_b: u64) -> Result<()> {
+        self.lp_fees_owed_a = self
+            .lp_fees_owed_a
+            .checked_sub(amount_a)
+            .ok_or(DarkFlowError::InsufficientBalance)?;
+        self.lp_fees_owed_b = self
+            .lp_fees_owed_b
+            .checked_sub(amount_b)
+            .ok_or(DarkFlowError::InsufficientBalance)?;
+        Ok(())
+    }
+
+    /// Verifying key version pinned for a circuit
+    pub fn pinned_vk_version(&self, circuit: CircuitKind) -> u32 {
+        match circuit {
+            CircuitKind::Swap => self.swap_vk_version,
+            CircuitKind::Execution => self.execution_vk_version,
+            CircuitKind::Position => self.position_vk_version,
+        }
+    }
+
+    /// Pin the verifying key version accepted for a circuit
+    pub fn pin_vk_version(&mut self, circuit: CircuitKind, version: u32) {
+        match circuit {
+            CircuitKind::Swap => self.swap_vk_version = version,
+            CircuitKind::Execution => self.execution_vk_version = version,
+            CircuitKind::Position => self.position_vk_version = version,
+        }
+    }
+}
+
+/// Ring buffer slot of an hour
+fn bucket_index(hour: i64) -> usize {
+    hour.rem_euclid(VOLUME_BUCKETS as i64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: i64 = 1_000_000;
+    const END: i64 = START + 4 * 86_400;
+
+    /// Zeroed pool ramping the amplification from `initial` to `future`
+    /// over `[START, END]`
+    fn ramping_pool(initial: u64, future: u64) -> DarkPool {
+        let mut pool = DarkPool::deserialize(&mut &[0u8; DarkPool::INIT_SPACE][..]).unwrap();
+        pool.curve = SwapCurve::StableSwap;
+        pool.initial_amplification = initial;
+        pool.future_amplification = future;
+        pool.initial_amplification_time = START;
+        pool.future_amplification_time = END;
+        pool
+    }
+
+    #[test]
+    fn amplification_ramps_up_linearly() {
+        let pool = ramping_pool(100, 1_000);
+        assert_eq!(pool.amplification(START - 1), 100);
+        assert_eq!(pool.amplification(START), 100);
+        assert_eq!(pool.amplification(START + (END - START) / 4), 325);
+        assert_eq!(pool.amplification((START + END) / 2), 550);
+        assert_eq!(pool.amplification(END), 1_000);
+        assert_eq!(pool.amplification(END + 1), 1_000);
+    }
+
+    #[test]
+    fn amplification_ramps_down_linearly() {
+        let pool = ramping_pool(1_000, 100);
+        assert_eq!(pool.amplification(START - 1), 1_000);
+        assert_eq!(pool.amplification(START), 1_000);
+        assert_eq!(pool.amplification(START + (END - START) / 4), 775);
+        assert_eq!(pool.amplification((START + END) / 2), 550);
+        assert_eq!(pool.amplification(END), 100);
+        assert_eq!(pool.amplification(END + 1), 100);
+    }
+
+    #[test]
+    fn amplification_moves_monotonically_along_the_ramp() {
+        let pool = ramping_pool(7, 61);
+        let mut previous = pool.amplification(START);
+        for now in (START..=END).step_by(997) {
+            let amplification = pool.amplification(now);
+            assert!(amplification >= previous && amplification <= 61, "{now}: {amplification}");
+            previous = amplification;
+        }
+    }
+
+    #[test]
+    fn settled_ramp_keeps_the_future_amplification() {
+        let mut pool = ramping_pool(100, 100);
+        pool.future_amplification_time = START;
+        assert_eq!(pool.amplification(START - 86_400), 100);
+        assert_eq!(pool.amplification(START + 86_400), 100);
     }
 }
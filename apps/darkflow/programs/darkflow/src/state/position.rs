@@ -1,9 +1,16 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
 
 /// Encrypted LP position
 ///
 /// The actual amounts are encrypted. Only the position owner
 /// can decrypt and prove ownership via ZK proofs.
+///
+/// The commitment is also a leaf of the pool's position tree, and
+/// withdrawals prove membership in the tree rather than naming this
+/// account. Records are written once and never updated: a spend is
+/// recorded by its nullifier, and whatever a partial withdrawal or fee
+/// claim leaves is committed to a new record at the next leaf index.
 #[account]
 #[derive(InitSpace)]
 pub struct EncryptedPosition {
@@ -14,26 +21,23 @@ pub struct EncryptedPosition {
     pub pool: Pubkey,
 
     /// Encrypted amount data (NaCl box encrypted)
-    /// Contains: amount_a, amount_b, share_percentage
+    /// Contains: amount_a, amount_b, shares
     #[max_len(256)]
     pub encrypted_data: Vec<u8>,
 
-    /// Commitment to the position (Pedersen commitment)
-    /// Used for ZK verification without revealing amounts
+    /// Commitment to the position's LP shares and fee checkpoint:
+    /// SHA-256(shares_le || fee_growth_a_le || fee_growth_b_le || note_commitment)
+    /// Withdrawals prove the share amount against it without storing it.
+    /// Concentrated positions commit to their liquidity, the fee growth
+    /// inside their range and the range note instead.
     pub commitment: [u8; 32],
 
-    /// Nullifier for this position (prevents double-withdraw)
-    pub nullifier: [u8; 32],
+    /// Index of the commitment in the pool's position tree
+    pub leaf_index: u32,
 
     /// Position entry timestamp
     pub created_at: i64,
 
-    /// Last update timestamp
-    pub updated_at: i64,
-
-    /// Whether position is active
-    pub is_active: bool,
-
     /// Bump seed for PDA
     pub bump: u8,
 }
@@ -45,35 +49,43 @@ impl EncryptedPosition {
         pool: Pubkey,
         encrypted_data: Vec<u8>,
         commitment: [u8; 32],
-        nullifier: [u8; 32],
+        leaf_index: u32,
         bump: u8,
     ) -> Result<Self> {
-        let now = Clock::get()?.unix_timestamp;
         Ok(Self {
             owner,
             pool,
             encrypted_data,
             commitment,
-            nullifier,
-            created_at: now,
-            updated_at: now,
-            is_active: true,
+            leaf_index,
+            created_at: Clock::get()?.unix_timestamp,
             bump,
         })
     }
 
-    /// Mark position as withdrawn
-    pub fn deactivate(&mut self) -> Result<()> {
-        self.is_active = false;
-        self.updated_at = Clock::get()?.unix_timestamp;
-        Ok(())
+    /// Commit to a share amount and its fee growth checkpoint under the
+    /// LP's note commitment
+    pub fn share_commitment(
+        shares: u64,
+        fee_growth_a: u128,
+        fee_growth_b: u128,
+        note_commitment: &[u8; 32],
+    ) -> [u8; 32] {
+        hashv(&[
+            &shares.to_le_bytes(),
+            &fee_growth_a.to_le_bytes(),
+            &fee_growth_b.to_le_bytes(),
+            note_commitment,
+        ])
+        .to_bytes()
     }
 
-    /// Update encrypted data (for partial withdrawals)
-    pub fn update_encrypted_data(&mut self, new_data: Vec<u8>, new_commitment: [u8; 32]) -> Result<()> {
-        self.encrypted_data = new_data;
-        self.commitment = new_commitment;
-        self.updated_at = Clock::get()?.unix_timestamp;
-        Ok(())
+    /// Bind a concentrated liquidity range to the LP's note commitment:
+    /// SHA-256(note_commitment || tick_lower_le || tick_upper_le)
+    ///
+    /// Range positions commit to this in place of the note commitment, so
+    /// the proof fixes the ticks the liquidity is removed from.
+    pub fn range_note(tick_lower: i32, tick_upper: i32, note_commitment: &[u8; 32]) -> [u8; 32] {
+        hashv(&[note_commitment, &tick_lower.to_le_bytes(), &tick_upper.to_le_bytes()]).to_bytes()
     }
 }
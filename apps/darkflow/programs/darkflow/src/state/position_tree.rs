@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use crate::errors::DarkFlowError;
+
+/// Depth of the position tree (2^20 leaves per pool)
+pub const POSITION_TREE_DEPTH: usize = 20;
+
+/// Number of recent roots a withdrawal proof may be made against
+pub const POSITION_ROOT_HISTORY: usize = 32;
+
+/// Incremental SHA-256 Merkle tree of LP position commitments
+///
+/// Deposits append their position commitment as a leaf. Withdrawal proofs
+/// show membership against a recent root instead of naming the position,
+/// so the tree root is the pool's `state_commitment`. Empty leaves are zero
+/// and each node is `SHA-256(left || right)`.
+#[account]
+#[derive(InitSpace)]
+pub struct PositionTree {
+    /// Pool this tree belongs to
+    pub pool: Pubkey,
+
+    /// Index of the next leaf to append
+    pub next_index: u32,
+
+    /// Rightmost filled node at each level, or the empty subtree root
+    pub filled_subtrees: [[u8; 32]; POSITION_TREE_DEPTH],
+
+    /// Ring buffer of recent roots
+    pub roots: [[u8; 32]; POSITION_ROOT_HISTORY],
+
+    /// Position of the current root in `roots`
+    pub current_root_index: u32,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl PositionTree {
+    /// Reset to the empty tree
+    pub fn initialize(&mut self, pool: Pubkey, bump: u8) {
+        let mut zero = [0u8; 32];
+        for subtree in self.filled_subtrees.iter_mut() {
+            *subtree = zero;
+            zero = hash_pair(&zero, &zero);
+        }
+
+        self.pool = pool;
+        self.next_index = 0;
+        self.roots = [[0u8; 32]; POSITION_ROOT_HISTORY];
+        self.roots[0] = zero;
+        self.current_root_index = 0;
+        self.bump = bump;
+    }
+
+    /// Current root
+    pub fn root(&self) -> [u8; 32] {
+        self.roots[self.current_root_index as usize]
+    }
+
+    /// Whether `root` is one of the recent roots
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        *root != [0u8; 32] && self.roots.iter().any(|known| known == root)
+    }
+
+    /// Append a leaf, returning its index
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u32> {
+        let index = self.next_index;
+        require!(
+            (index as u64) < 1u64 << POSITION_TREE_DEPTH,
+            DarkFlowError::PositionTreeFull
+        );
+
+        let mut node = leaf;
+        let mut zero = [0u8; 32];
+        let mut path = index;
+        for subtree in self.filled_subtrees.iter_mut() {
+            node = if path & 1 == 0 {
+                *subtree = node;
+                hash_pair(&node, &zero)
+            } else {
+                hash_pair(subtree, &node)
+            };
+            zero = hash_pair(&zero, &zero);
+            path >>= 1;
+        }
+
+        self.current_root_index = (self.current_root_index + 1) % POSITION_ROOT_HISTORY as u32;
+        self.roots[self.current_root_index as usize] = node;
+        self.next_index = index + 1;
+
+        Ok(index)
+    }
+}
+
+/// Hash two sibling nodes
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hashv(&[left, right]).to_bytes()
+}
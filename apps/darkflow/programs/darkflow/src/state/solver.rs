@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+
+/// Maximum number of solvers a pool can register
+pub const MAX_SOLVERS: usize = 16;
+
+/// Time between a solver's unbond request and its bond withdrawal, leaving
+/// the authority a window to slash fills made before the request
+pub const SOLVER_UNBOND_COOLDOWN: i64 = 7 * 86_400;
+
+/// A solver registered with a pool
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct SolverEntry {
+    /// Solver signing key
+    pub solver: Pubkey,
+
+    /// Bond posted in lamports, held by the registry account
+    pub bond: u64,
+
+    /// Whether the pool authority has activated this solver
+    pub is_active: bool,
+
+    /// Number of dark orders filled
+    pub orders_filled: u64,
+
+    /// Registration timestamp
+    pub registered_at: i64,
+
+    /// When the solver asked to unbond (0 if not unbonding)
+    pub unbond_requested_at: i64,
+}
+
+impl SolverEntry {
+    /// Whether the solver has asked to unbond
+    pub fn is_unbonding(&self) -> bool {
+        self.unbond_requested_at != 0
+    }
+}
+
+/// Solvers allowed to execute dark orders for a pool
+///
+/// Solvers register by posting a bond, the pool authority activates them,
+/// and only active solvers can execute orders. Removing a solver returns
+/// its bond and slashing one sends it to the pool treasury. A solver can
+/// leave on its own by unbonding, which deactivates it and releases the
+/// bond after `SOLVER_UNBOND_COOLDOWN`.
+#[account]
+#[derive(InitSpace)]
+pub struct SolverRegistry {
+    /// Pool this registry belongs to
+    pub pool: Pubkey,
+
+    /// Minimum bond (lamports) required to register
+    pub min_bond: u64,
+
+    /// Registered solvers
+    #[max_len(MAX_SOLVERS)]
+    pub solvers: Vec<SolverEntry>,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl SolverRegistry {
+    /// Find a registered solver
+    pub fn find(&self, solver: &Pubkey) -> Option<&SolverEntry> {
+        self.solvers.iter().find(|entry| entry.solver == *solver)
+    }
+
+    /// Find a registered solver for update
+    pub fn find_mut(&mut self, solver: &Pubkey) -> Result<&mut SolverEntry> {
+        self.solvers
+            .iter_mut()
+            .find(|entry| entry.solver == *solver)
+            .ok_or(DarkFlowError::SolverNotRegistered.into())
+    }
+
+    /// Remove a registered solver, returning its entry
+    pub fn remove(&mut self, solver: &Pubkey) -> Result<SolverEntry> {
+        let index = self
+            .solvers
+            .iter()
+            .position(|entry| entry.solver == *solver)
+            .ok_or(DarkFlowError::SolverNotRegistered)?;
+        Ok(self.solvers.swap_remove(index))
+    }
+
+    /// Check if a solver is registered and active
+    pub fn is_active_solver(&self, solver: &Pubkey) -> bool {
+        self.find(solver).is_some_and(|entry| entry.is_active)
+    }
+
+    /// Record an order fill by a solver
+    pub fn record_fill(&mut self, solver: &Pubkey) -> Result<()> {
+        let entry = self.find_mut(solver)?;
+        entry.orders_filled = entry.orders_filled.saturating_add(1);
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+use crate::tick_math::add_liquidity_delta;
+
+/// Number of ticks a pool can have initialized at once
+pub const MAX_INITIALIZED_TICKS: usize = 100;
+
+/// Smallest liquidity a concentrated position may hold, so filling the
+/// shared tick array with positions ties up real capital rather than dust
+pub const MIN_POSITION_LIQUIDITY: u64 = 1_000_000_000;
+
+/// Liquidity bookkeeping for one initialized tick
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TickInfo {
+    /// Tick index
+    pub index: i32,
+
+    /// Total liquidity of the positions bounded by this tick
+    pub liquidity_gross: u128,
+
+    /// Liquidity added when the price crosses this tick upwards
+    pub liquidity_net: i128,
+
+    /// Token A fee growth on the other side of this tick from the current price
+    pub fee_growth_outside_a: u128,
+
+    /// Token B fee growth on the other side of this tick from the current price
+    pub fee_growth_outside_b: u128,
+}
+
+/// One 64-tick word of the tick bitmap
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TickBitmapWord {
+    /// Word position: compressed tick (tick / tick_spacing) >> 6
+    pub word: i16,
+
+    /// Bit `i` is set when compressed tick `word * 64 + i` is initialized
+    pub bits: u64,
+}
+
+/// Public tick liquidity of a concentrated liquidity pool
+///
+/// Only aggregates per tick are stored: which position contributed to a
+/// tick stays inside the position commitments. Swaps walk the bitmap to
+/// find the next initialized tick and cross it to update the pool's
+/// active liquidity.
+#[account]
+#[derive(InitSpace)]
+pub struct PoolTicks {
+    /// Pool these ticks belong to
+    pub pool: Pubkey,
+
+    /// Initialized ticks, sorted by index
+    #[max_len(MAX_INITIALIZED_TICKS)]
+    pub ticks: Vec<TickInfo>,
+
+    /// Non-empty bitmap words, sorted by word position
+    #[max_len(MAX_INITIALIZED_TICKS)]
+    pub bitmap: Vec<TickBitmapWord>,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl PoolTicks {
+    /// Initialized tick at `index`, if any
+    pub fn get(&self, index: i32) -> Option<&TickInfo> {
+        self.ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .ok()
+            .map(|position| &self.ticks[position])
+    }
+
+    /// Add `liquidity_delta` to a position boundary
+    ///
+    /// A tick is initialized on first use, with its outside fee growth
+    /// taken to have accrued entirely below it when the current price is at
+    /// or above it, and cleared again once nothing references it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        index: i32,
+        tick_spacing: u16,
+        tick_current: i32,
+        liquidity_delta: i128,
+        fee_growth_a: u128,
+        fee_growth_b: u128,
+        is_upper: bool,
+    ) -> Result<()> {
+        let position = match self.ticks.binary_search_by_key(&index, |tick| tick.index) {
+            Ok(position) => position,
+            Err(position) => {
+                require!(
+                    self.ticks.len() < MAX_INITIALIZED_TICKS,
+                    DarkFlowError::TickStateFull
+                );
+                let (outside_a, outside_b) = if index <= tick_current {
+                    (fee_growth_a, fee_growth_b)
+                } else {
+                    (0, 0)
+                };
+                self.ticks.insert(
+                    position,
+                    TickInfo {
+                        index,
+                        fee_growth_outside_a: outside_a,
+                        fee_growth_outside_b: outside_b,
+                        ..Default::default()
+                    },
+                );
+                self.flip(index, tick_spacing)?;
+                position
+            }
+        };
+
+        let tick = &mut self.ticks[position];
+        tick.liquidity_gross = add_liquidity_delta(tick.liquidity_gross, liquidity_delta)?;
+        tick.liquidity_net = if is_upper {
+            tick.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            tick.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(DarkFlowError::MathOverflow)?;
+
+        if tick.liquidity_gross == 0 {
+            self.ticks.remove(position);
+            self.flip(index, tick_spacing)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cross an initialized tick, returning its net liquidity
+    pub fn cross(&mut self, index: i32, fee_growth_a: u128, fee_growth_b: u128) -> Result<i128> {
+        let position = self
+            .ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .map_err(|_| error!(DarkFlowError::InvalidTickRange))?;
+        let tick = &mut self.ticks[position];
+        tick.fee_growth_outside_a = fee_growth_a.wrapping_sub(tick.fee_growth_outside_a);
+        tick.fee_growth_outside_b = fee_growth_b.wrapping_sub(tick.fee_growth_outside_b);
+        Ok(tick.liquidity_net)
+    }
+
+    /// Fee growth accrued inside `[tick_lower, tick_upper)` (wrapping)
+    pub fn fee_growth_inside(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        tick_current: i32,
+        fee_growth_a: u128,
+        fee_growth_b: u128,
+    ) -> (u128, u128) {
+        let outside = |index: i32| {
+            self.get(index)
+                .map(|tick| (tick.fee_growth_outside_a, tick.fee_growth_outside_b))
+                .unwrap_or_default()
+        };
+        let (lower_a, lower_b) = outside(tick_lower);
+        let (upper_a, upper_b) = outside(tick_upper);
+
+        let (below_a, below_b) = if tick_current >= tick_lower {
+            (lower_a, lower_b)
+        } else {
+            (fee_growth_a.wrapping_sub(lower_a), fee_growth_b.wrapping_sub(lower_b))
+        };
+        let (above_a, above_b) = if tick_current < tick_upper {
+            (upper_a, upper_b)
+        } else {
+            (fee_growth_a.wrapping_sub(upper_a), fee_growth_b.wrapping_sub(upper_b))
+        };
+
+        (
+            fee_growth_a.wrapping_sub(below_a).wrapping_sub(above_a),
+            fee_growth_b.wrapping_sub(below_b).wrapping_sub(above_b),
+        )
+    }
+
+    /// Next initialized tick at or below `tick` (`lte`), or above it
+    pub fn next_initialized_tick(&self, tick: i32, tick_spacing: u16, lte: bool) -> Option<i32> {
+        let spacing = tick_spacing as i32;
+        let compressed = if lte {
+            tick.div_euclid(spacing)
+        } else {
+            tick.div_euclid(spacing) + 1
+        };
+        let (word, bit) = bitmap_position(compressed);
+
+        let found = if lte {
+            // Bits at or below `bit` in this word, then lower words
+            let mask = if bit == 63 { u64::MAX } else { (1u64 << (bit + 1)) - 1 };
+            self.bitmap
+                .iter()
+                .rev()
+                .filter(|entry| entry.word <= word)
+                .map(|entry| {
+                    let bits = if entry.word == word { entry.bits & mask } else { entry.bits };
+                    (entry.word, bits)
+                })
+                .find(|(_, bits)| *bits != 0)
+                .map(|(word, bits)| (word, 63 - bits.leading_zeros()))
+        } else {
+            // Bits at or above `bit` in this word, then higher words
+            let mask = !((1u64 << bit) - 1);
+            self.bitmap
+                .iter()
+                .filter(|entry| entry.word >= word)
+                .map(|entry| {
+                    let bits = if entry.word == word { entry.bits & mask } else { entry.bits };
+                    (entry.word, bits)
+                })
+                .find(|(_, bits)| *bits != 0)
+                .map(|(word, bits)| (word, bits.trailing_zeros()))
+        };
+
+        found.map(|(word, bit)| (word as i32 * 64 + bit as i32) * spacing)
+    }
+
+    /// Toggle a tick's bit, dropping words that become empty
+    fn flip(&mut self, index: i32, tick_spacing: u16) -> Result<()> {
+        let (word, bit) = bitmap_position(index.div_euclid(tick_spacing as i32));
+        match self.bitmap.binary_search_by_key(&word, |entry| entry.word) {
+            Ok(position) => {
+                self.bitmap[position].bits ^= 1 << bit;
+                if self.bitmap[position].bits == 0 {
+                    self.bitmap.remove(position);
+                }
+            }
+            Err(position) => {
+                require!(
+                    self.bitmap.len() < MAX_INITIALIZED_TICKS,
+                    DarkFlowError::TickStateFull
+                );
+                self.bitmap.insert(position, TickBitmapWord { word, bits: 1 << bit });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bitmap word and bit of a compressed tick
+fn bitmap_position(compressed: i32) -> (i16, u32) {
+    (compressed.div_euclid(64) as i16, compressed.rem_euclid(64) as u32)
+}
@@ -0,0 +1,82 @@
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+
+/// Maximum size of a serialized Groth16 verifying key (up to 24 public inputs)
+pub const MAX_VERIFYING_KEY_LEN: usize = 2048;
+
+/// DarkFlow circuits whose proofs are verified on-chain
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace, Debug)]
+pub enum CircuitKind {
+    /// `circuits/swap` — dark swap validity
+    Swap,
+    /// `circuits/execution` — dark order execution
+    Execution,
+    /// `circuits/position` — LP position ownership
+    Position,
+}
+
+/// Verifying key registry for one circuit of a pool
+///
+/// Keys are uploaded in chunks into a staging buffer, then activated,
+/// which bumps the version. The key it replaces is retained as the
+/// previous version, so a pool pinned to it keeps accepting proofs made
+/// against it until the pool pins the new version. Each activation drops
+/// the key two versions back.
+#[account]
+#[derive(InitSpace)]
+pub struct VerifyingKeyRegistry {
+    /// Pool this registry belongs to
+    pub pool: Pubkey,
+
+    /// Circuit this key verifies
+    pub circuit: CircuitKind,
+
+    /// Version of the active key (0 = none activated yet)
+    pub version: u32,
+
+    /// SHA-256 of the active key, for auditors to compare against the circuit build
+    pub key_hash: [u8; 32],
+
+    /// Active serialized Groth16 verifying key
+    #[max_len(MAX_VERIFYING_KEY_LEN)]
+    pub key_data: Vec<u8>,
+
+    /// Version of the key replaced by the last activation (0 = none)
+    pub previous_version: u32,
+
+    /// SHA-256 of the previous key
+    pub previous_key_hash: [u8; 32],
+
+    /// Previous serialized Groth16 verifying key, kept for migration
+    #[max_len(MAX_VERIFYING_KEY_LEN)]
+    pub previous_key_data: Vec<u8>,
+
+    /// Key being uploaded, promoted to `key_data` on activation
+    #[max_len(MAX_VERIFYING_KEY_LEN)]
+    pub staged_key_data: Vec<u8>,
+
+    /// Timestamp of the last activation
+    pub updated_at: i64,
+
+    /// Bump seed for PDA
+    pub bump: u8,
+}
+
+impl VerifyingKeyRegistry {
+    /// Return the retained key of the version the pool has pinned
+    pub fn pinned_key(&self, pinned_version: u32) -> Result<&[u8]> {
+        require!(pinned_version > 0, DarkFlowError::VerifyingKeyVersionMismatch);
+        if pinned_version == self.version {
+            Ok(&self.key_data)
+        } else if pinned_version == self.previous_version {
+            Ok(&self.previous_key_data)
+        } else {
+            err!(DarkFlowError::VerifyingKeyVersionMismatch)
+        }
+    }
+
+    /// Whether a key of `version` is retained and can be pinned
+    pub fn has_version(&self, version: u32) -> bool {
+        version > 0 && (version == self.version || version == self.previous_version)
+    }
+}
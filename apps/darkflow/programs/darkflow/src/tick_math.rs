@@ -0,0 +1,423 @@
+//! Fixed-point math for concentrated liquidity.
+//!
+//! Prices follow Uniswap v3: tick `i` is the price `1.0001^i` of token A
+//! in token B, and pools track its square root as a Q64.64 number
+//! (`sqrt_price = sqrt(1.0001^i) * 2^64`). A position with liquidity `L`
+//! between `sqrt_lower` and `sqrt_upper` holds, with `sqrt_price` clamped
+//! to the range:
+//!   - token A: `L * (sqrt_upper - sqrt_price) / (sqrt_price * sqrt_upper)`
+//!   - token B: `L * (sqrt_price - sqrt_lower)`
+//!
+//! Products of two Q64.64 values need 256 bits, so `mul_div` carries them
+//! in two `u128` halves.
+
+use anchor_lang::prelude::*;
+use crate::errors::DarkFlowError;
+
+/// Lowest tick, where `sqrt_price` still fits above 2^32
+pub const MIN_TICK: i32 = -443_636;
+
+/// Highest tick, where `sqrt_price` still fits below 2^96
+pub const MAX_TICK: i32 = 443_636;
+
+/// Largest tick spacing a pool may use
+pub const MAX_TICK_SPACING: u16 = 16_384;
+
+/// Q64.64 one
+const Q64: u128 = 1 << 64;
+
+/// `sqrt(1.0001)^-(2^i)` as Q0.128, for each bit `i` of the tick
+const INVERSE_SQRT_POWERS: [u128; 20] = [
+    0xfffcb933bd6fad37aa2d162d1a594001,
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x09aa508b5b7a84e1c677de54f3e99bc9,
+    0x005d6af8dedb81196699c329225ee604,
+    0x00002216e584f5fa1ea926041bedfe98,
+    0x00000000048a170391f7dc42444e8fa2,
+];
+
+/// Result of swapping within one initialized-tick interval
+pub struct SwapStep {
+    /// Square root price reached
+    pub sqrt_price_next: u128,
+    /// Input consumed, excluding the fee
+    pub amount_in: u64,
+    /// Output produced
+    pub amount_out: u64,
+    /// Fee charged on the input
+    pub fee_amount: u64,
+}
+
+/// Q64.64 square root price of a tick
+pub fn sqrt_price_at_tick(tick: i32) -> Result<u128> {
+    require!(
+        (MIN_TICK..=MAX_TICK).contains(&tick),
+        DarkFlowError::InvalidTickRange
+    );
+
+    // Multiply out in Q0.128, starting just below one. Every factor is
+    // below one, so the product keeps 128 bits of precision even where the
+    // price is tiny
+    let magnitude = tick.unsigned_abs();
+    let mut ratio = u128::MAX;
+    for (bit, factor) in INVERSE_SQRT_POWERS.iter().enumerate() {
+        if magnitude & (1 << bit) != 0 {
+            ratio = full_mul(ratio, *factor).0;
+        }
+    }
+
+    // Back to Q64.64, rounded up: the ratio itself for negative ticks, its
+    // inverse (2^192 / ratio) for positive ones
+    if tick > 0 {
+        mul_div_ceil(1 << 96, 1 << 96, ratio)
+    } else {
+        Ok((ratio >> 64) + u128::from(ratio as u64 != 0))
+    }
+}
+
+/// Greatest tick whose square root price is at most `sqrt_price`
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Result<i32> {
+    require!(
+        sqrt_price >= sqrt_price_at_tick(MIN_TICK)? && sqrt_price <= sqrt_price_at_tick(MAX_TICK)?,
+        DarkFlowError::InvalidTickRange
+    );
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(mid)? <= sqrt_price {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+/// `a * b / denominator`, rounded down
+pub fn mul_div(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    mul_div_rem(a, b, denominator)
+        .map(|(quotient, _)| quotient)
+        .ok_or(DarkFlowError::MathOverflow.into())
+}
+
+/// `a * b / denominator`, rounded up
+pub fn mul_div_ceil(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    let (quotient, remainder) =
+        mul_div_rem(a, b, denominator).ok_or(DarkFlowError::MathOverflow)?;
+    if remainder == 0 {
+        Ok(quotient)
+    } else {
+        quotient.checked_add(1).ok_or(DarkFlowError::MathOverflow.into())
+    }
+}
+
+/// Token A held by `liquidity` between two square root prices
+pub fn amount_a_delta(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let (lower, upper) = ordered(sqrt_price_a, sqrt_price_b);
+    require!(lower > 0, DarkFlowError::InvalidTickRange);
+
+    if round_up {
+        let scaled = mul_div_ceil(liquidity, upper - lower, upper)?;
+        mul_div_ceil(scaled, Q64, lower)
+    } else {
+        let scaled = mul_div(liquidity, upper - lower, upper)?;
+        mul_div(scaled, Q64, lower)
+    }
+}
+
+/// Token B held by `liquidity` between two square root prices
+pub fn amount_b_delta(sqrt_price_a: u128, sqrt_price_b: u128, liquidity: u128, round_up: bool) -> Result<u128> {
+    let (lower, upper) = ordered(sqrt_price_a, sqrt_price_b);
+    if round_up {
+        mul_div_ceil(liquidity, upper - lower, Q64)
+    } else {
+        mul_div(liquidity, upper - lower, Q64)
+    }
+}
+
+/// Token amounts backing `liquidity` over `[tick_lower, tick_upper)` at the
+/// current price
+pub fn position_amounts(
+    sqrt_price: u128,
+    tick_current: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u64,
+    round_up: bool,
+) -> Result<(u64, u64)> {
+    let sqrt_lower = sqrt_price_at_tick(tick_lower)?;
+    let sqrt_upper = sqrt_price_at_tick(tick_upper)?;
+    let liquidity = liquidity as u128;
+
+    let (amount_a, amount_b) = if tick_current < tick_lower {
+        (amount_a_delta(sqrt_lower, sqrt_upper, liquidity, round_up)?, 0)
+    } else if tick_current < tick_upper {
+        (
+            amount_a_delta(sqrt_price, sqrt_upper, liquidity, round_up)?,
+            amount_b_delta(sqrt_lower, sqrt_price, liquidity, round_up)?,
+        )
+    } else {
+        (0, amount_b_delta(sqrt_lower, sqrt_upper, liquidity, round_up)?)
+    };
+
+    Ok((to_u64(amount_a)?, to_u64(amount_b)?))
+}
+
+/// Apply a signed liquidity change
+pub fn add_liquidity_delta(liquidity: u128, delta: i128) -> Result<u128> {
+    if delta >= 0 {
+        liquidity.checked_add(delta as u128)
+    } else {
+        liquidity.checked_sub(delta.unsigned_abs())
+    }
+    .ok_or(DarkFlowError::MathOverflow.into())
+}
+
+/// Swap as much of `amount_remaining` as fits before `sqrt_price_target`
+///
+/// The fee is taken from the input, like `calculate_swap_output`, and
+/// rounded so the pool never gives away value.
+pub fn compute_swap_step(
+    sqrt_price: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee_bps: u16,
+) -> Result<SwapStep> {
+    let a_to_b = sqrt_price_target <= sqrt_price;
+    let fee_factor = 10000 - fee_bps as u128;
+    let remaining_less_fee = amount_remaining as u128 * fee_factor / 10000;
+
+    let max_in = if a_to_b {
+        amount_a_delta(sqrt_price_target, sqrt_price, liquidity, true)?
+    } else {
+        amount_b_delta(sqrt_price, sqrt_price_target, liquidity, true)?
+    };
+
+    let sqrt_price_next = if remaining_less_fee >= max_in {
+        sqrt_price_target
+    } else {
+        next_sqrt_price_from_input(sqrt_price, liquidity, remaining_less_fee, a_to_b)?
+    };
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, amount_out) = if a_to_b {
+        (
+            if reached_target {
+                max_in
+            } else {
+                amount_a_delta(sqrt_price_next, sqrt_price, liquidity, true)?
+            },
+            amount_b_delta(sqrt_price_next, sqrt_price, liquidity, false)?,
+        )
+    } else {
+        (
+            if reached_target {
+                max_in
+            } else {
+                amount_b_delta(sqrt_price, sqrt_price_next, liquidity, true)?
+            },
+            amount_a_delta(sqrt_price, sqrt_price_next, liquidity, false)?,
+        )
+    };
+    let mut amount_in = to_u64(amount_in)?;
+
+    // Short of the target the whole remainder is spent, so the rest is fee
+    let fee_amount = if !reached_target {
+        amount_in = amount_in.min(amount_remaining);
+        amount_remaining - amount_in
+    } else if amount_in == 0 || fee_bps == 0 {
+        0
+    } else {
+        to_u64(mul_div_ceil(amount_in as u128, fee_bps as u128, fee_factor)?)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out: to_u64(amount_out)?,
+        fee_amount,
+    })
+}
+
+/// Square root price after adding `amount_in` to the pool
+///
+/// Rounds against the trader: selling token A lowers the price by no more
+/// than the exact amount, and selling token B raises it by no more.
+fn next_sqrt_price_from_input(sqrt_price: u128, liquidity: u128, amount_in: u128, a_to_b: bool) -> Result<u128> {
+    if amount_in == 0 {
+        return Ok(sqrt_price);
+    }
+
+    if a_to_b {
+        // sqrt_price' = L * sqrt_price / (L + amount_in * sqrt_price)
+        let denominator = liquidity
+            .checked_add(mul_div(amount_in, sqrt_price, Q64)?)
+            .ok_or(DarkFlowError::MathOverflow)?;
+        mul_div_ceil(liquidity, sqrt_price, denominator)
+    } else {
+        // sqrt_price' = sqrt_price + amount_in / L
+        sqrt_price
+            .checked_add(mul_div(amount_in, Q64, liquidity)?)
+            .ok_or(DarkFlowError::MathOverflow.into())
+    }
+}
+
+/// `a * b / denominator` with its remainder, or None on overflow
+pub(crate) fn mul_div_rem(a: u128, b: u128, denominator: u128) -> Option<(u128, u128)> {
+    if denominator == 0 {
+        return None;
+    }
+
+    let (high, low) = full_mul(a, b);
+    if high == 0 {
+        return Some((low / denominator, low % denominator));
+    }
+    if high >= denominator {
+        return None;
+    }
+
+    // Shift-subtract long division of the 256-bit product
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1;
+        }
+    }
+    Some((quotient, remainder))
+}
+
+/// Full 256-bit product as (high, low) halves
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+
+    let (a_high, a_low) = (a >> 64, a & MASK);
+    let (b_high, b_low) = (b >> 64, b & MASK);
+
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (high_low & MASK) + (low_high & MASK);
+    let low = (middle << 64) | (low_low & MASK);
+    let high = high_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64);
+    (high, low)
+}
+
+fn ordered(a: u128, b: u128) -> (u128, u128) {
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| error!(DarkFlowError::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sqrt(1.0001^tick) * 2^64` in floating point
+    fn expected_sqrt_price(tick: i32) -> f64 {
+        1.0001f64.powf(tick as f64 / 2.0) * Q64 as f64
+    }
+
+    /// Ticks spread over the whole range, with the bit boundaries
+    fn sample_ticks() -> Vec<i32> {
+        let mut ticks: Vec<i32> = (MIN_TICK..=MAX_TICK).step_by(7_919).collect();
+        for bit in 0..20 {
+            ticks.extend([1 << bit, -(1 << bit), (1 << bit) - 1, 1 - (1 << bit)]);
+        }
+        ticks.extend([MIN_TICK, MIN_TICK + 1, -1, 0, 1, MAX_TICK - 1, MAX_TICK]);
+        ticks.retain(|tick| (MIN_TICK..=MAX_TICK).contains(tick));
+        ticks
+    }
+
+    #[test]
+    fn sqrt_price_matches_the_tick_price() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), Q64);
+
+        // Within 1e-10, except that near MIN_TICK the price is about 2^32
+        // units, where one unit of Q64.64 resolution is already 2.3e-10
+        for tick in sample_ticks() {
+            let actual = sqrt_price_at_tick(tick).unwrap() as f64;
+            let expected = expected_sqrt_price(tick);
+            let error = (actual - expected).abs();
+            assert!(
+                error <= expected * 1e-10 || error <= 1.0,
+                "tick {}: {} vs {} ({:e})",
+                tick,
+                actual,
+                expected,
+                error / expected
+            );
+        }
+    }
+
+    #[test]
+    fn sqrt_price_is_strictly_increasing() {
+        let mut previous = sqrt_price_at_tick(MIN_TICK).unwrap();
+        for tick in (MIN_TICK + 1..=MAX_TICK).step_by(101) {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert!(sqrt_price > previous, "tick {}", tick);
+            previous = sqrt_price;
+        }
+    }
+
+    #[test]
+    fn tick_at_sqrt_price_round_trips() {
+        for tick in sample_ticks() {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+
+            // Prices inside a tick round down to it
+            if tick < MAX_TICK {
+                let next = sqrt_price_at_tick(tick + 1).unwrap();
+                assert_eq!(tick_at_sqrt_price(next - 1).unwrap(), tick);
+                assert_eq!(tick_at_sqrt_price((sqrt_price + next) / 2).unwrap(), tick);
+            }
+        }
+    }
+
+    #[test]
+    fn ticks_outside_the_range_are_rejected() {
+        assert!(sqrt_price_at_tick(MIN_TICK - 1).is_err());
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+
+        let min = sqrt_price_at_tick(MIN_TICK).unwrap();
+        let max = sqrt_price_at_tick(MAX_TICK).unwrap();
+        assert!(tick_at_sqrt_price(min - 1).is_err());
+        assert!(tick_at_sqrt_price(max + 1).is_err());
+    }
+
+    #[test]
+    fn mul_div_rounds_in_the_requested_direction() {
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX).unwrap(), u128::MAX);
+        assert_eq!(mul_div(Q64 * 3, Q64 * 5, Q64).unwrap(), Q64 * 15);
+        assert_eq!(mul_div(7, 3, 2).unwrap(), 10);
+        assert_eq!(mul_div_ceil(7, 3, 2).unwrap(), 11);
+        assert_eq!(mul_div_ceil(8, 3, 2).unwrap(), 12);
+        assert!(mul_div(u128::MAX, 2, 1).is_err());
+        assert!(mul_div(1, 1, 0).is_err());
+    }
+}
@@ -1,56 +1,57 @@
-/// ZK proof verification module for DarkFlow.
-///
-/// In development mode (`dev` feature), proofs are verified using blake3 hash
-/// commitments — fast but NOT zero-knowledge.
-///
-/// In production mode (default), proofs are verified using SHA-256 based
-/// commitments matching the Noir circuits in `circuits/`. When the Noir
-/// toolchain is available, these will be replaced with Groth16/UltraPlonk
-/// verification using the Solana `alt_bn128` precompile.
-///
-/// Circuit specifications:
-///   - `circuits/swap/`      — dark swap validity proof
-///   - `circuits/execution/` — order execution correctness proof
-///   - `circuits/position/`  — LP position ownership proof
+//! ZK proof verification module for DarkFlow.
+//!
+//! By default, proofs are Groth16 proofs over BN254 for the Noir circuits in
+//! `circuits/`, verified with the Solana `alt_bn128` syscalls (see
+//! `groth16`). Every 32-byte public value is exposed by the circuits as two
+//! field elements (see `groth16::pack_bytes32`).
+//!
+//! In development mode (`dev` feature, off by default), a proof is instead a
+//! blake3 hash of the same public inputs under a per-circuit domain tag —
+//! fast but NOT zero-knowledge, and trivially forgeable. It binds exactly the
+//! statement the Groth16 verifier checks, so a dev build never accepts a
+//! statement a production build would reject for its public inputs.
+//!
+//! Verifying keys are not compiled in: each pool stores them in a
+//! `VerifyingKeyRegistry` per circuit and pins the version it accepts.
+//!
+//! Circuit specifications:
+//!   - `circuits/swap/`      — dark swap validity proof
+//!   - `circuits/execution/` — order execution correctness proof
+//!   - `circuits/position/`  — LP position ownership proof
+
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::prelude::Pubkey;
+use crate::groth16;
 
 /// Verify a dark swap ZK proof.
 ///
 /// The proof demonstrates:
 /// 1. The user has sufficient balance for the swap
-/// 2. The swap amount matches the encrypted order
-/// 3. The nullifier is correctly derived (prevents replay)
+/// 2. The nullifier is derived from the encrypted order (prevents replay)
+/// 3. The proof is bound to this pool and to the settled amounts
 ///
-/// Proof layout: 32 bytes verification hash
-/// In production: Noir proof bytes (variable length, verified via Groth16)
-pub fn verify_swap_proof(proof: &[u8], encrypted_order: &[u8], nullifier: &[u8; 32]) -> bool {
-    if proof.len() < 32 {
-        return false;
-    }
+/// Public inputs: `SHA-256(encrypted_order)`, `nullifier`, `SHA-256(pool)`,
+/// `input_amount`, `min_output_amount`
+pub fn verify_swap_proof(
+    proof: &[u8],
+    encrypted_order: &[u8],
+    nullifier: &[u8; 32],
+    pool: &Pubkey,
+    input_amount: u64,
+    min_output_amount: u64,
+    verifying_key: &[u8],
+) -> bool {
+    let encrypted_order_hash = hash(encrypted_order).to_bytes();
+    let pool_state_hash = hash(pool.as_ref()).to_bytes();
 
-    #[cfg(feature = "dev")]
-    {
-        // Dev mode: blake3 commitment check (NOT zero-knowledge)
-        let mut input = Vec::with_capacity(encrypted_order.len() + 32 + 13);
-        input.extend_from_slice(encrypted_order);
-        input.extend_from_slice(nullifier);
-        input.extend_from_slice(b"darkflow_swap");
-        let expected = blake3::hash(&input);
-        return proof[..32] == *expected.as_bytes();
-    }
+    let mut public_inputs = Vec::with_capacity(8);
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(&encrypted_order_hash));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(nullifier));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(&pool_state_hash));
+    public_inputs.push(groth16::u64_input(input_amount));
+    public_inputs.push(groth16::u64_input(min_output_amount));
 
-    #[cfg(not(feature = "dev"))]
-    {
-        // Production: SHA-256 commitment matching Noir circuit public inputs.
-        // The proof must contain SHA-256(encrypted_order || nullifier || "darkflow_swap")
-        // which matches the Noir circuit's encrypted_order_hash public input.
-        use anchor_lang::solana_program::hash::hash;
-        let mut input = Vec::with_capacity(encrypted_order.len() + 32 + 13);
-        input.extend_from_slice(encrypted_order);
-        input.extend_from_slice(nullifier);
-        input.extend_from_slice(b"darkflow_swap");
-        let expected = hash(&input);
-        proof[..32] == expected.to_bytes()
-    }
+    verify_statement(proof, &public_inputs, verifying_key, b"darkflow_swap")
 }
 
 /// Verify a dark order execution proof.
@@ -60,67 +61,124 @@ pub fn verify_swap_proof(proof: &[u8], encrypted_order: &[u8], nullifier: &[u8;
 /// 2. The output amount meets the user's minimum
 /// 3. The execution is faithful to the original order
 ///
-/// Proof layout: 32 bytes verification hash
-/// In production: Noir proof bytes (variable length, verified via Groth16)
-pub fn verify_execution_proof(proof: &[u8], commitment: &[u8; 32]) -> bool {
-    if proof.len() < 32 {
-        return false;
-    }
+/// Public inputs: `commitment`, `SHA-256(output_amount_le)`
+pub fn verify_execution_proof(
+    proof: &[u8],
+    commitment: &[u8; 32],
+    output_amount: u64,
+    verifying_key: &[u8],
+) -> bool {
+    let output_amount_hash = hash(&output_amount.to_le_bytes()).to_bytes();
 
-    #[cfg(feature = "dev")]
-    {
-        // Dev mode: blake3 commitment check
-        let mut input = [0u8; 50];
-        input[..32].copy_from_slice(commitment);
-        input[32..50].copy_from_slice(b"darkflow_execution");
-        let expected = blake3::hash(&input);
-        return proof[..32] == *expected.as_bytes();
-    }
+    let mut public_inputs = Vec::with_capacity(4);
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(commitment));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(&output_amount_hash));
 
-    #[cfg(not(feature = "dev"))]
-    {
-        // Production: SHA-256 commitment matching Noir circuit
-        use anchor_lang::solana_program::hash::hash;
-        let mut input = [0u8; 50];
-        input[..32].copy_from_slice(commitment);
-        input[32..50].copy_from_slice(b"darkflow_execution");
-        let expected = hash(&input);
-        proof[..32] == expected.to_bytes()
-    }
+    verify_statement(proof, &public_inputs, verifying_key, b"darkflow_execution")
+}
+
+/// Public statement of a position spend (withdrawal or fee claim)
+///
+/// Holds only what the spend pays out and what it leaves behind. The
+/// position's shares and fee checkpoints stay private witnesses, so the
+/// spend cannot be matched to the deposit that opened the position.
+pub struct PositionStatement<'a> {
+    /// Position tree root the proof is made against
+    pub root: &'a [u8; 32],
+    /// Nullifier of the spent position
+    pub nullifier: &'a [u8; 32],
+    /// Token A account receiving the payout
+    pub recipient_a: &'a Pubkey,
+    /// Token B account receiving the payout
+    pub recipient_b: &'a Pubkey,
+    /// Shares withdrawn (liquidity for a range position), 0 for a fee claim
+    pub withdrawn_shares: u64,
+    /// Token A fees paid out
+    pub fees_a: u64,
+    /// Token B fees paid out
+    pub fees_b: u64,
+    /// Lower tick of a range position (equal to `tick_upper` for full-range
+    /// shares). Public because the withdrawal moves liquidity off it.
+    pub tick_lower: i32,
+    /// Upper tick of a range position
+    pub tick_upper: i32,
+    /// Commitment to the remaining shares (zero when nothing remains)
+    pub change_commitment: &'a [u8; 32],
+    /// Current token A fee growth (inside the range for a range position):
+    /// what the fees are measured up to, and the change commitment's
+    /// checkpoint
+    pub fee_growth_a: u128,
+    /// Current token B fee growth (inside the range for a range position)
+    pub fee_growth_b: u128,
 }
 
 /// Verify a position ownership proof.
 ///
-/// The proof demonstrates:
-/// 1. The caller owns the position identified by the commitment
-/// 2. The position has non-zero value
-/// 3. The commitment was correctly constructed
+/// The proof demonstrates, for private shares and fee checkpoints:
+/// 1. The position commitment is a leaf of the position tree with `root`
+/// 2. The nullifier is derived from the LP's secret and the leaf index
+/// 3. The position holds at least `withdrawn_shares`
+/// 4. `fees_a` and `fees_b` are the fees all of its shares earned between
+///    its checkpoints and the current fee growth
+/// 5. `change_commitment` opens to the shares left after the withdrawal,
+///    checkpointed at the current fee growth (zero on a full exit)
+/// 6. For a range position, both commitments are made under the range note
+///    of `tick_lower` and `tick_upper`, and the remaining liquidity is zero
+///    or at least `MIN_POSITION_LIQUIDITY`
 ///
-/// Proof layout: 32 bytes verification hash
-/// In production: Noir proof bytes (variable length, verified via Groth16)
-pub fn verify_position_proof(proof: &[u8], commitment: &[u8; 32]) -> bool {
-    if proof.len() < 32 {
-        return false;
-    }
+/// The proof is bound to the recipient token accounts and the payouts, so
+/// it cannot be redirected or altered by whoever submits it.
+///
+/// Public inputs: `root`, `nullifier`, `SHA-256(recipient_a || recipient_b)`,
+/// `withdrawn_shares`, `fees_a`, `fees_b`, `tick_lower`, `tick_upper`,
+/// `change_commitment`, `fee_growth_a`, `fee_growth_b`
+pub fn verify_position_proof(
+    proof: &[u8],
+    statement: &PositionStatement,
+    verifying_key: &[u8],
+) -> bool {
+    let recipient_hash =
+        hashv(&[statement.recipient_a.as_ref(), statement.recipient_b.as_ref()]).to_bytes();
 
+    let mut public_inputs = Vec::with_capacity(14);
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(statement.root));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(statement.nullifier));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(&recipient_hash));
+    public_inputs.push(groth16::u64_input(statement.withdrawn_shares));
+    public_inputs.push(groth16::u64_input(statement.fees_a));
+    public_inputs.push(groth16::u64_input(statement.fees_b));
+    public_inputs.push(groth16::u64_input(statement.tick_lower as u32 as u64));
+    public_inputs.push(groth16::u64_input(statement.tick_upper as u32 as u64));
+    public_inputs.extend_from_slice(&groth16::pack_bytes32(statement.change_commitment));
+    public_inputs.push(groth16::u128_input(statement.fee_growth_a));
+    public_inputs.push(groth16::u128_input(statement.fee_growth_b));
+
+    verify_statement(proof, &public_inputs, verifying_key, b"darkflow_position")
+}
+
+/// Check a proof of the statement given by `public_inputs`
+///
+/// Production builds verify a Groth16 proof against `verifying_key`. Dev
+/// builds expect the blake3 hash of `domain` followed by every public input.
+fn verify_statement(proof: &[u8], public_inputs: &[[u8; 32]], verifying_key: &[u8], domain: &[u8]) -> bool {
     #[cfg(feature = "dev")]
     {
-        // Dev mode: blake3 commitment check
-        let mut input = [0u8; 50];
-        input[..32].copy_from_slice(commitment);
-        input[32..50].copy_from_slice(b"darkflow_position");
-        let expected = blake3::hash(&input);
-        return proof[..32] == *expected.as_bytes();
+        // Dev mode: blake3 commitment check (NOT zero-knowledge)
+        let _ = verifying_key;
+        if proof.len() < 32 {
+            return false;
+        }
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(domain);
+        for input in public_inputs {
+            hasher.update(input);
+        }
+        proof[..32] == *hasher.finalize().as_bytes()
     }
 
     #[cfg(not(feature = "dev"))]
     {
-        // Production: SHA-256 commitment matching Noir circuit
-        use anchor_lang::solana_program::hash::hash;
-        let mut input = [0u8; 50];
-        input[..32].copy_from_slice(commitment);
-        input[32..50].copy_from_slice(b"darkflow_position");
-        let expected = hash(&input);
-        proof[..32] == expected.to_bytes()
+        let _ = domain;
+        groth16::verify(proof, public_inputs, verifying_key)
     }
 }
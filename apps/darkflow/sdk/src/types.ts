@@ -68,8 +68,6 @@ export interface EncryptedPositionState {
   encryptedData: Uint8Array;
   /** Position commitment */
   commitment: Uint8Array;
-  /** Is position active */
-  isActive: boolean;
   /** Creation timestamp */
   createdAt: number;
 }